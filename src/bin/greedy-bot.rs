@@ -0,0 +1,17 @@
+//! The greedy expander from `warlib::strategy::greedy`, always taking the
+//! cheapest region next to it. See `warlib::play_reference()`.
+//!
+//!     greedy-bot [seed]
+
+extern crate env_logger;
+extern crate warlib;
+
+use std::env;
+use std::process;
+
+fn main() {
+    env::set_var("RUST_LOG", "info");
+    env_logger::init().unwrap();
+    let seed = env::args().nth(1).and_then(|seed| seed.parse().ok()).unwrap_or_else(warlib::default_seed);
+    process::exit(warlib::play_reference("greedy", seed));
+}
@@ -0,0 +1,17 @@
+//! The turtle from `warlib::strategy::turtle`, which guards its borders and
+//! only grows where it is safe. See `warlib::play_reference()`.
+//!
+//!     turtle-bot [seed]
+
+extern crate env_logger;
+extern crate warlib;
+
+use std::env;
+use std::process;
+
+fn main() {
+    env::set_var("RUST_LOG", "info");
+    env_logger::init().unwrap();
+    let seed = env::args().nth(1).and_then(|seed| seed.parse().ok()).unwrap_or_else(warlib::default_seed);
+    process::exit(warlib::play_reference("turtle", seed));
+}
@@ -4,22 +4,86 @@ extern crate env_logger;
 extern crate warlib;
 
 use std::env;
-use std::io::BufRead;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+use warlib::eval::EvalWeights;
+use warlib::replay::ReplayWriter;
+use warlib::strategy::{self, Strategy, StrategyOptions};
 
 fn main() {
     pre_boot();
-    let (tx, rx) = warlib::Bot::spawn();
-
-    let stdin = std::io::stdin();
-    for line in stdin.lock().lines() {
-        tx.send(line.unwrap()).unwrap()
-    }
+    let (strategy, seed, replay, dump_dir) = match parse_args() {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            error!("{}", e);
+            process::exit(2);
+        }
+    };
+    process::exit(warlib::play_stdio(strategy, seed, replay, dump_dir));
+}
 
-    std::thread::spawn(move || {
-        for response in rx.iter() {
-            println!("{}", response);
+/// Reads `--strategy <name>`, `--weights <file>` and `--seed <n>`, playing
+/// randomly with the default weights when they aren't given. The seed falls
+/// back to `WARBOT_SEED` and then to a random one. `--replay <file>` records
+/// the game there, with a snapshot of the map every round if `--replay-maps`
+/// is given too. `--dump-maps <dir>` writes the map there every round as DOT
+/// and JSON, the file names starting with the bot's name. `--iterations <n>`
+/// makes the MCTS strategy search exactly that many iterations rather than
+/// for as long as the clock allows, which it needs to play the same game
+/// again from the same seed. `--plan-picks` has the planner choose its
+/// starting regions by playing out the rest of the picking phase.
+fn parse_args() -> Result<(Box<Strategy + Send>, u64, Option<ReplayWriter<Box<Write + Send>>>, Option<PathBuf>), String> {
+    let mut name = "random".to_owned();
+    let mut options = StrategyOptions::default();
+    let mut seed = None;
+    let mut replay_path = None;
+    let mut snapshots = false;
+    let mut dump_dir = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--strategy" => name = try!(args.next().ok_or("--strategy needs a name".to_owned())),
+            "--weights" => {
+                let path = try!(args.next().ok_or("--weights needs a file".to_owned()));
+                options.weights = try!(EvalWeights::load(&path));
+                info!("loaded weights from {}", path);
+            },
+            "--seed" => {
+                let value = try!(args.next().ok_or("--seed needs a number".to_owned()));
+                seed = Some(try!(value.parse().map_err(|_| format!("--seed needs a number, got {}", value))));
+            },
+            "--iterations" => {
+                let value = try!(args.next().ok_or("--iterations needs a number".to_owned()));
+                options.iterations = Some(try!(value.parse().map_err(|_| format!("--iterations needs a number, got {}", value))));
+            },
+            "--plan-picks" => options.plan_picks = true,
+            "--replay" => replay_path = Some(try!(args.next().ok_or("--replay needs a file".to_owned()))),
+            "--replay-maps" => snapshots = true,
+            "--dump-maps" => {
+                let dir = try!(args.next().ok_or("--dump-maps needs a directory".to_owned()));
+                info!("writing the map to {} every round", dir);
+                dump_dir = Some(PathBuf::from(dir));
+            },
+            _ => return Err(format!("unknown argument {}", arg))
         }
-    });
+    }
+    let replay = match replay_path {
+        Some(path) => {
+            info!("recording the game to {}", path);
+            Some(try!(ReplayWriter::create(&path, snapshots).map_err(|e| format!("couldn't create {}: {}", path, e))))
+        },
+        None => None
+    };
+    info!("playing with the {} strategy", name);
+    if options.iterations.is_some() && name != "mcts" {
+        return Err("--iterations only applies to --strategy mcts".to_owned());
+    }
+    if options.plan_picks && name != "planner" {
+        return Err("--plan-picks only applies to --strategy planner".to_owned());
+    }
+    let strategy = try!(strategy::by_name(&name, &options).ok_or(format!("unknown strategy {}", name)));
+    Ok((strategy, seed.unwrap_or_else(warlib::default_seed), replay, dump_dir))
 }
 
 fn pre_boot() {
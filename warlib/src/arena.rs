@@ -0,0 +1,122 @@
+//! Whole games between two bots in this process.
+//!
+//! A `Match` starts a `Bot` for each side with `Bot::start_with()` and lets
+//! `engine::run_game()` referee them through their channels, exactly as it
+//! would bots in other processes: same protocol, same fog, same timebanks.
+//! Nothing is spawned but two threads per game, so it is quick enough for
+//! tuning and for tests that need many games.
+//!
+//! `game::play()` is quicker still, it skips the protocol and calls the
+//! strategies directly, but then the bots' own bookkeeping is never
+//! exercised.
+
+use engine::{EngineConfig, Player, run_game};
+use game::GameResult;
+use state::GameState;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Duration;
+use strategy::{Strategy, seeded_rng};
+use {Bot, BotHandle};
+
+/// A bot on its own thread, spoken to through its channels.
+pub struct BotPlayer {
+    handle: BotHandle,
+    output: Receiver<String>
+}
+
+#[derive(Debug)]
+pub struct Match {
+    pub setup: GameState,
+    pub config: EngineConfig,
+    /// Seeds the engine's dice and both bots' random choices.
+    pub seed: u64
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchReport {
+    pub result: GameResult,
+    /// The engine's log of the game, see `engine`.
+    pub replay: Vec<String>,
+    /// Why each bot crashed, if it did.
+    pub crashes: [Option<String>; 2]
+}
+
+impl BotPlayer {
+    pub fn start(strategy: Box<Strategy + Send>, seed: u64) -> BotPlayer {
+        let (handle, output) = Bot::start_with(strategy, seed);
+        BotPlayer {
+            handle: handle,
+            output: output
+        }
+    }
+
+    /// Stops the bot, `Err` with the panic message if it crashed.
+    pub fn join(self) -> Result<(), String> {
+        self.handle.join()
+    }
+}
+
+impl Player for BotPlayer {
+    fn send(&mut self, line: &str) {
+        if self.handle.send(line.to_owned()).is_err() {
+            warn!("bot stopped reading");
+        }
+    }
+
+    fn request(&mut self, line: &str, timeout: Duration) -> Option<String> {
+        // Anything already waiting answered an earlier request too late.
+        loop {
+            match self.output.try_recv() {
+                Ok(late) => warn!("dropping late answer '{}'", late),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => break
+            }
+        }
+        self.send(line);
+        self.output.recv_timeout(timeout).ok()
+    }
+}
+
+impl Match {
+    pub fn new(setup: GameState, seed: u64) -> Match {
+        Match {
+            setup: setup,
+            config: EngineConfig::default(),
+            seed: seed
+        }
+    }
+
+    /// Plays `first` as player1 against `second`.
+    pub fn play(&self, first: Box<Strategy + Send>, second: Box<Strategy + Send>) -> MatchReport {
+        let mut first = BotPlayer::start(first, self.seed);
+        let mut second = BotPlayer::start(second, self.seed ^ 1);
+        let (result, replay) = run_game(&self.setup, [&mut first, &mut second], &self.config, &mut seeded_rng(self.seed));
+        MatchReport {
+            result: result,
+            replay: replay,
+            crashes: [first.join().err(), second.join().err()]
+        }
+    }
+}
+
+#[test]
+fn matches_are_refereed() {
+    use game::{TEST_SETUP, load_setup};
+    use strategy::{PlannerConfig, PlannerStrategy, RandomStrategy};
+    let game = Match::new(load_setup(TEST_SETUP).unwrap(), 4);
+    let report = game.play(Box::new(PlannerStrategy::new(PlannerConfig::default())), Box::new(RandomStrategy::new()));
+    assert_eq!(report.crashes, [None, None]);
+    assert!(report.result.rounds > 0);
+    assert!(report.replay.iter().all(|line| !line.ends_with("timed out")));
+    assert!(report.replay.last().unwrap().starts_with("result"));
+}
+
+#[test]
+fn matches_are_repeatable() {
+    use game::{TEST_SETUP, load_setup};
+    use strategy::RandomStrategy;
+    let game = Match::new(load_setup(TEST_SETUP).unwrap(), 9);
+    let replays: Vec<Vec<String>> = (0..2)
+        .map(|_| game.play(Box::new(RandomStrategy::new()), Box::new(RandomStrategy::new())).replay)
+        .collect();
+    assert_eq!(replays[0], replays[1]);
+}
@@ -0,0 +1,81 @@
+//! Turns a recorded game into a fodder file for `tests/runner.rs`.
+//!
+//!     replay-to-fodder [options] <replay or game log>
+//!
+//! Takes either a replay written by `warbot --replay` or a game log from
+//! `warlight-engine` or `tournament`, and prints the fodder file, see
+//! `warlib::fodder`.
+//!
+//! Options:
+//!
+//!     --round <n>          last round kept, the whole game by default
+//!     --placements         end at the placements of that round
+//!     --player <name>      whose side of a game log to take, player1
+//!     --strategy <name>    strategy the test plays, random by default
+//!     --valid <spec>       the `# Valid:` line, by default the last answer
+//!                          must not come back
+//!     --output <file>      write the file there rather than to stdout
+
+extern crate warlib;
+
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process;
+use warlib::fodder::{FodderOptions, from_engine_log, from_replay, is_replay, write_fodder};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut options = FodderOptions::default();
+    let mut player = "player1".to_owned();
+    let mut output = None;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            path = Some(arg);
+            continue;
+        }
+        if arg == "--placements" {
+            options.placements_only = true;
+            continue;
+        }
+        let value = try!(args.next().ok_or(format!("{} needs a value", arg)));
+        match arg.as_ref() {
+            "--round" => options.round = Some(try!(value.parse().map_err(|_| format!("--round needs a number, got {}", value)))),
+            "--player" => player = value,
+            "--strategy" => options.strategy = Some(value),
+            "--valid" => options.valid = Some(value),
+            "--output" => output = Some(value),
+            _ => return Err(format!("unknown argument {}", arg))
+        }
+    }
+    let path = try!(path.ok_or("usage: replay-to-fodder [options] <replay or game log>".to_owned()));
+
+    let mut contents = String::new();
+    try!(File::open(&path).and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path, e)));
+    let recording = if is_replay(&contents) {
+        try!(from_replay(&contents))
+    } else {
+        try!(from_engine_log(&contents, &player))
+    };
+    options.source = path.clone();
+    let fodder = try!(write_fodder(&recording, &options).map_err(|e| format!("{}: {}", path, e)));
+
+    match output {
+        Some(output) => File::create(&output)
+            .and_then(|mut file| file.write_all(fodder.as_bytes()))
+            .map_err(|e| format!("couldn't write {}: {}", output, e)),
+        None => {
+            print!("{}", fodder);
+            Ok(())
+        }
+    }
+}
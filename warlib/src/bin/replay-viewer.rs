@@ -0,0 +1,133 @@
+//! Shows a recorded game round by round, see `warlib::viewer`.
+//!
+//!     replay-viewer [options] <replay or game log>
+//!
+//! Takes a replay written by `warbot --replay` or a game log from
+//! `warlight-engine` and prints every round, or steps through them.
+//!
+//! Options:
+//!
+//!     --round <n>        only show that round
+//!     --step             show a round at a time, enter for the next, p for
+//!                        the previous, a number to jump there, q to quit
+//!     --html <file>      write a page to step through in a browser instead
+//!     --player <name>    whose side of a game log to show, player1
+
+extern crate warlib;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::process;
+use warlib::fodder::{from_engine_log, from_replay, is_replay};
+use warlib::viewer::{self, Timeline};
+
+struct Options {
+    round: Option<u64>,
+    step: bool,
+    html: Option<String>,
+    player: String,
+    path: String
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let options = try!(parse_args());
+    let mut contents = String::new();
+    try!(File::open(&options.path).and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", options.path, e)));
+    let recording = if is_replay(&contents) {
+        try!(from_replay(&contents))
+    } else {
+        try!(from_engine_log(&contents, &options.player))
+    };
+    let timeline = viewer::timeline(&recording);
+    if timeline.frames.is_empty() {
+        return Err(format!("{} has no rounds", options.path));
+    }
+
+    if let Some(ref path) = options.html {
+        let page = viewer::html(&timeline, &options.path);
+        return File::create(path)
+            .and_then(|mut file| file.write_all(page.as_bytes()))
+            .map_err(|e| format!("couldn't write {}: {}", path, e));
+    }
+    let start = match options.round {
+        Some(round) => try!(timeline.frames.iter().position(|frame| frame.round == round)
+            .ok_or(format!("there is no round {}", round))),
+        None => 0
+    };
+    if options.step {
+        step(&timeline, start);
+    } else if options.round.is_some() {
+        print!("{}", viewer::text(&timeline, &timeline.frames[start]));
+    } else {
+        for frame in timeline.frames.iter() {
+            println!("{}", viewer::text(&timeline, frame));
+        }
+    }
+    Ok(())
+}
+
+fn step(timeline: &Timeline, start: usize) {
+    let stdin = io::stdin();
+    let mut current = start;
+    loop {
+        // Clears the terminal first.
+        print!("\x1b[2J\x1b[H{}", viewer::text(timeline, &timeline.frames[current]));
+        print!("\n[enter] next, p previous, <round> jump, q quit > ");
+        io::stdout().flush().unwrap_or(());
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+        match line.trim() {
+            "q" => return,
+            "p" => current = current.saturating_sub(1),
+            "" => current = (current + 1).min(timeline.frames.len() - 1),
+            other => if let Some(index) = other.parse().ok()
+                .and_then(|round: u64| timeline.frames.iter().position(|frame| frame.round == round)) {
+                current = index;
+            }
+        }
+    }
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        round: None,
+        step: false,
+        html: None,
+        player: "player1".to_owned(),
+        path: String::new()
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            options.path = arg;
+            continue;
+        }
+        if arg == "--step" {
+            options.step = true;
+            continue;
+        }
+        let value = try!(args.next().ok_or(format!("{} needs a value", arg)));
+        match arg.as_ref() {
+            "--round" => options.round = Some(try!(value.parse().map_err(|_| format!("--round needs a number, got {}", value)))),
+            "--html" => options.html = Some(value),
+            "--player" => options.player = value,
+            _ => return Err(format!("unknown argument {}", arg))
+        }
+    }
+    if options.path.is_empty() {
+        return Err("usage: replay-viewer [options] <replay or game log>".to_owned());
+    }
+    Ok(options)
+}
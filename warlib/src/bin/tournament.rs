@@ -0,0 +1,310 @@
+//! Plays bots against each other on many maps and rates them.
+//!
+//!     tournament [options] --bot <name>=<command> --bot ... <transcript>...
+//!
+//! Every bot is a command line started as a child process for each game, like
+//! `warlight-engine` does, e.g. `--bot "planner=target/debug/warbot --strategy
+//! planner"`. Maps come from the transcripts or map files given. Every
+//! pairing plays the same number of games on every map, swapping sides
+//! between games, spread over a few threads. Each bot is given a seed for
+//! the game in `WARBOT_SEED`, so the same `--seed` plays the same games
+//! again.
+//!
+//! At the end every bot's share of the points, with a 95% confidence
+//! interval, and its rating from `ratings::fit_ratings()` are printed along
+//! with how each pairing went.
+//!
+//! Options:
+//!
+//!     --gauntlet <name>      only play the named bot against the others,
+//!                            every bot plays every other by default
+//!     --games <n>            games per pairing and map, 2
+//!     --threads <n>          games played at once, 4
+//!     --seed <n>             seed for all the games, random by default
+//!     --rounds <n>           cap on the rounds per game
+//!     --timebank <ms>        most a bot's timebank can hold, 10000
+//!     --time-per-move <ms>   added to the timebank after every answer, 500
+//!     --replays <dir>        write every game's log to this directory
+
+extern crate warlib;
+
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::channel;
+use std::thread;
+use warlib::engine::{EngineConfig, ProcessPlayer, run_game};
+use warlib::game::load_setup;
+use warlib::ratings::{Score, elo_difference, fit_ratings, wilson_interval};
+use warlib::state::GameState;
+use warlib::strategy::seeded_rng;
+
+/// z for a 95% confidence interval.
+const Z: f64 = 1.96;
+
+struct Bot {
+    name: String,
+    command: String
+}
+
+struct Options {
+    bots: Vec<Bot>,
+    gauntlet: Option<usize>,
+    games: u64,
+    threads: usize,
+    seed: u64,
+    rounds: Option<u64>,
+    config: EngineConfig,
+    replays: Option<String>,
+    transcripts: Vec<String>
+}
+
+/// A single game to play, `players[0]` is player1.
+#[derive(Clone)]
+struct Job {
+    players: [usize; 2],
+    map: usize,
+    game: u64,
+    seed: u64
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    let mut setups = Vec::new();
+    for path in options.transcripts.iter() {
+        match read_setup(path, options.rounds) {
+            Ok(setup) => setups.push(setup),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(2);
+            }
+        }
+    }
+
+    let jobs = schedule(&options, setups.len());
+    println!("seed {}, {} games", options.seed, jobs.len());
+    let scores = play_all(jobs, &options, setups);
+    report(&options.bots, &scores);
+}
+
+/// Every game of the tournament, sides swapping from one game to the next.
+fn schedule(options: &Options, maps: usize) -> Vec<Job> {
+    let mut pairings = Vec::new();
+    for first in 0..options.bots.len() {
+        for second in first + 1..options.bots.len() {
+            match options.gauntlet {
+                Some(challenger) if first != challenger && second != challenger => {},
+                _ => pairings.push((first, second))
+            }
+        }
+    }
+    let mut jobs = Vec::new();
+    for &(first, second) in pairings.iter() {
+        for map in 0..maps {
+            for game in 0..options.games {
+                let players = if game % 2 == 0 {[first, second]} else {[second, first]};
+                jobs.push(Job {
+                    players: players,
+                    map: map,
+                    game: game,
+                    // Both orders of a pairing get the same dice.
+                    seed: options.seed ^ ((map as u64) << 32) ^ (game / 2)
+                });
+            }
+        }
+    }
+    jobs
+}
+
+/// Plays `jobs` on `options.threads` threads, returning `scores[i][j]`, bot
+/// i's score against bot j. Every game goes in from both sides, as
+/// `fit_ratings()` expects.
+fn play_all(jobs: Vec<Job>, options: &Options, setups: Vec<GameState>) -> Vec<Vec<Score>> {
+    let total = jobs.len();
+    let queue = Arc::new(Mutex::new(jobs));
+    let setups = Arc::new(setups);
+    let commands: Arc<Vec<(String, String)>> = Arc::new(options.bots.iter()
+        .map(|bot| (bot.name.clone(), bot.command.clone()))
+        .collect());
+    let (tx, rx) = channel();
+    for _ in 0..options.threads.max(1) {
+        let queue = queue.clone();
+        let setups = setups.clone();
+        let commands = commands.clone();
+        let config = options.config.clone();
+        let replays = options.replays.clone();
+        let tx = tx.clone();
+        thread::spawn(move || loop {
+            let job = match queue.lock().unwrap().pop() {
+                Some(job) => job,
+                None => return
+            };
+            let outcome = play(&job, &setups[job.map], &commands, &config, replays.as_ref());
+            if tx.send((job, outcome)).is_err() {
+                return;
+            }
+        });
+    }
+    drop(tx);
+
+    let mut scores = vec![vec![Score::default(); options.bots.len()]; options.bots.len()];
+    let mut done = 0;
+    for (job, outcome) in rx.iter() {
+        done += 1;
+        let (first, second) = (job.players[0], job.players[1]);
+        match outcome {
+            Ok(winner) => {
+                let mut score = Score::default();
+                match winner {
+                    Some(0) => score.wins += 1,
+                    Some(_) => score.losses += 1,
+                    None => score.draws += 1
+                }
+                scores[first][second].add(&score);
+                scores[second][first].add(&score.reversed());
+                let verdict = winner.map_or("draw".to_owned(), |winner| format!("{} won", options.bots[job.players[winner]].name));
+                println!("[{}/{}] {} vs {} on map {}: {}", done, total, options.bots[first].name, options.bots[second].name, job.map, verdict);
+            },
+            Err(e) => eprintln!("[{}/{}] {} vs {} on map {}: {}", done, total, options.bots[first].name, options.bots[second].name, job.map, e)
+        }
+    }
+    scores
+}
+
+/// Plays a single game, returning the winning side.
+fn play(job: &Job, setup: &GameState, commands: &[(String, String)], config: &EngineConfig, replays: Option<&String>) -> Result<Option<usize>, String> {
+    let mut first = try!(ProcessPlayer::start(&commands[job.players[0]].1, job.seed));
+    let mut second = try!(ProcessPlayer::start(&commands[job.players[1]].1, job.seed ^ 1));
+    let (result, log) = run_game(setup, [&mut first, &mut second], config, &mut seeded_rng(job.seed));
+    if let Some(dir) = replays {
+        let name = format!("{}-{}-{}-{}.log", commands[job.players[0]].0, commands[job.players[1]].0, job.map, job.game);
+        let path = Path::new(dir).join(name);
+        try!(File::create(&path)
+            .and_then(|mut file| file.write_all(log.join("\n").as_bytes()).and_then(|_| file.write_all(b"\n")))
+            .map_err(|e| format!("couldn't write {}: {}", path.display(), e)));
+    }
+    Ok(result.winner)
+}
+
+fn report(bots: &[Bot], scores: &[Vec<Score>]) {
+    let ratings = fit_ratings(scores);
+    let mut order: Vec<usize> = (0..bots.len()).collect();
+    order.sort_by(|a, b| ratings[*b].partial_cmp(&ratings[*a]).unwrap());
+
+    println!("");
+    println!("{:<16} {:>7} {:>9} {:>7}   95% interval", "bot", "rating", "points", "share");
+    for &i in order.iter() {
+        let mut total = Score::default();
+        for score in scores[i].iter() {
+            total.add(score);
+        }
+        if total.games() == 0 {
+            continue;
+        }
+        let (low, high) = wilson_interval(&total, Z);
+        println!(
+            "{:<16} {:>7.0} {:>5}/{:<3} {:>6.1}%   {:.1}% - {:.1}%",
+            bots[i].name,
+            ratings[i],
+            total.points(),
+            total.games(),
+            100.0 * total.rate(),
+            100.0 * low,
+            100.0 * high
+        );
+    }
+
+    println!("");
+    for (position, &i) in order.iter().enumerate() {
+        for &j in order[position + 1..].iter() {
+            let score = scores[i][j];
+            if score.games() == 0 {
+                continue;
+            }
+            let (low, high) = wilson_interval(&score, Z);
+            println!(
+                "{} vs {}: +{} ={} -{}, {:.1}% ({:.1}% - {:.1}%), {:+.0} Elo",
+                bots[i].name,
+                bots[j].name,
+                score.wins,
+                score.draws,
+                score.losses,
+                100.0 * score.rate(),
+                100.0 * low,
+                100.0 * high,
+                elo_difference(score.rate())
+            );
+        }
+    }
+}
+
+fn read_setup(path: &str, rounds: Option<u64>) -> Result<GameState, String> {
+    let mut contents = String::new();
+    try!(File::open(path).and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path, e)));
+    let mut setup = try!(load_setup(&contents).map_err(|e| format!("{}: {}", path, e)));
+    if let Some(rounds) = rounds {
+        setup.settings.max_rounds = setup.settings.max_rounds.min(rounds);
+    }
+    Ok(setup)
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        bots: Vec::new(),
+        gauntlet: None,
+        games: 2,
+        threads: 4,
+        seed: warlib::default_seed(),
+        rounds: None,
+        config: EngineConfig::default(),
+        replays: None,
+        transcripts: Vec::new()
+    };
+    let mut gauntlet = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            options.transcripts.push(arg);
+            continue;
+        }
+        let value = try!(args.next().ok_or(format!("{} needs a value", arg)));
+        let number = || value.parse::<u64>().map_err(|_| format!("{} needs a number, got {}", arg, value));
+        match arg.as_ref() {
+            "--bot" => {
+                let split = try!(value.find('=').ok_or(format!("--bot needs <name>=<command>, got {}", value)));
+                let (name, command) = value.split_at(split);
+                options.bots.push(Bot {
+                    name: name.to_owned(),
+                    command: command[1..].to_owned()
+                });
+            },
+            "--gauntlet" => gauntlet = Some(value.clone()),
+            "--games" => options.games = try!(number()),
+            "--threads" => options.threads = try!(number()) as usize,
+            "--seed" => options.seed = try!(number()),
+            "--rounds" => options.rounds = Some(try!(number())),
+            "--timebank" => options.config.timebank = try!(number()),
+            "--time-per-move" => options.config.time_per_move = try!(number()),
+            "--replays" => options.replays = Some(value.clone()),
+            _ => return Err(format!("unknown argument {}", arg))
+        }
+    }
+    if options.bots.len() < 2 || options.transcripts.is_empty() {
+        return Err("usage: tournament [options] --bot <name>=<command> --bot ... <transcript>...".to_owned());
+    }
+    if let Some(name) = gauntlet {
+        let index = try!(options.bots.iter().position(|bot| bot.name == name).ok_or(format!("no bot called {}", name)));
+        options.gauntlet = Some(index);
+    }
+    Ok(options)
+}
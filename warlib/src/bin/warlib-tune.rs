@@ -0,0 +1,237 @@
+//! Tunes `EvalWeights` by self-play.
+//!
+//!     warlib-tune [options] <transcript>...
+//!
+//! Maps are read from the `setup_map` lines of the transcripts given, the
+//! fodder files under `tests/fodder` will do, or from map files. Every
+//! generation each candidate plays the current champion with the MCTS
+//! strategy, swapping sides between games, one thread per candidate. A
+//! candidate that beats the champion more often than not takes its place and
+//! is written to the output file, then the next generation is bred from the
+//! best half by crossover and mutation.
+//!
+//! Options:
+//!
+//!     --generations <n>  generations to run, 10 by default
+//!     --population <n>   candidates per generation, 8 by default
+//!     --games <n>        games each candidate plays per generation, 4
+//!     --iterations <n>   MCTS iterations per move, 50
+//!     --rounds <n>       cap on the rounds per game, 40
+//!     --seed <n>         seed for everything, 0
+//!     --start <file>     weights to start from instead of the defaults
+//!     --output <file>    where to write the best weights, weights.txt
+
+extern crate rand;
+extern crate warlib;
+
+use rand::{Rng, XorShiftRng};
+use rand::distributions::{IndependentSample, Normal};
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process;
+use std::sync::Arc;
+use std::sync::mpsc::channel;
+use std::thread;
+use warlib::eval::EvalWeights;
+use warlib::game::{load_setup, play};
+use warlib::state::GameState;
+use warlib::strategy::{MctsConfig, MctsStrategy, seeded_rng};
+
+/// Spread of the factor weights are multiplied by when mutated.
+const MUTATION: f64 = 0.25;
+
+struct Options {
+    generations: u64,
+    population: usize,
+    games: u64,
+    iterations: u64,
+    rounds: u64,
+    seed: u64,
+    start: EvalWeights,
+    output: String,
+    transcripts: Vec<String>
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    let mut setups = Vec::new();
+    for path in options.transcripts.iter() {
+        match read_setup(path, options.rounds) {
+            Ok(setup) => setups.push(setup),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(2);
+            }
+        }
+    }
+    let setups = Arc::new(setups);
+
+    let mut rng = seeded_rng(options.seed);
+    let mut champion = options.start.clone();
+    let mut population: Vec<EvalWeights> = (0..options.population).map(|_| mutate(&champion, &mut rng)).collect();
+
+    for generation in 0..options.generations {
+        let scores = score_population(&population, &champion, &setups, &options, generation);
+        let mut ranked: Vec<(f64, EvalWeights)> = scores.into_iter().zip(population.into_iter()).collect();
+        ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+        println!("generation {}: best {:.3}, worst {:.3}", generation, ranked[0].0, ranked[ranked.len() - 1].0);
+        if ranked[0].0 > 0.5 {
+            champion = ranked[0].1.clone();
+            println!("new champion:\n{}", champion);
+            if let Err(e) = write_weights(&options.output, &champion) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+
+        let parents: Vec<EvalWeights> = ranked.into_iter()
+            .take((options.population / 2).max(1))
+            .map(|(_, weights)| weights)
+            .collect();
+        population = (0..options.population).map(|_| {
+            let child = crossover(rng.choose(&parents).unwrap(), rng.choose(&parents).unwrap(), &mut rng);
+            mutate(&child, &mut rng)
+        }).collect();
+    }
+
+    if let Err(e) = write_weights(&options.output, &champion) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    println!("wrote {}", options.output);
+}
+
+/// Share of the games against the champion each candidate won, draws
+/// counting half.
+fn score_population(population: &[EvalWeights], champion: &EvalWeights, setups: &Arc<Vec<GameState>>, options: &Options, generation: u64) -> Vec<f64> {
+    let (tx, rx) = channel();
+    for (index, candidate) in population.iter().enumerate() {
+        let tx = tx.clone();
+        let candidate = candidate.clone();
+        let champion = champion.clone();
+        let setups = setups.clone();
+        let games = options.games;
+        let iterations = options.iterations;
+        let seed = options.seed ^ (generation << 32) ^ ((index as u64) << 16);
+        thread::spawn(move || {
+            let mut points = 0.0;
+            for game in 0..games {
+                let game_seed = seed ^ game;
+                let setup = &setups[(game as usize + index) % setups.len()];
+                let mut ours = strategy(&candidate, iterations);
+                let mut theirs = strategy(&champion, iterations);
+                let side = (game % 2) as usize;
+                let result = if side == 0 {
+                    play(setup, [&mut ours, &mut theirs], &mut seeded_rng(game_seed))
+                } else {
+                    play(setup, [&mut theirs, &mut ours], &mut seeded_rng(game_seed))
+                };
+                points += match result.winner {
+                    Some(winner) if winner == side => 1.0,
+                    Some(_) => 0.0,
+                    None => 0.5
+                };
+            }
+            tx.send((index, points / games.max(1) as f64)).unwrap();
+        });
+    }
+    drop(tx);
+
+    let mut scores = vec![0.0; population.len()];
+    for (index, score) in rx.iter() {
+        scores[index] = score;
+    }
+    scores
+}
+
+fn strategy(weights: &EvalWeights, iterations: u64) -> MctsStrategy {
+    let mut config = MctsConfig::default();
+    config.eval = weights.clone();
+    config.iterations = Some(iterations);
+    MctsStrategy::new(config)
+}
+
+/// Every weight taken from one of the parents at random.
+fn crossover(first: &EvalWeights, second: &EvalWeights, rng: &mut XorShiftRng) -> EvalWeights {
+    let mut child = first.clone();
+    for (name, value) in second.values() {
+        if rng.gen() {
+            child.set(name, value).unwrap();
+        }
+    }
+    child
+}
+
+/// Every weight multiplied by a random factor around 1, which keeps its sign.
+fn mutate(weights: &EvalWeights, rng: &mut XorShiftRng) -> EvalWeights {
+    let normal = Normal::new(0.0, MUTATION);
+    let mut mutated = weights.clone();
+    for (name, value) in weights.values() {
+        mutated.set(name, value * normal.ind_sample(rng).exp()).unwrap();
+    }
+    mutated
+}
+
+fn read_setup(path: &str, rounds: u64) -> Result<GameState, String> {
+    let mut contents = String::new();
+    try!(File::open(path).and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path, e)));
+    let mut setup = try!(load_setup(&contents).map_err(|e| format!("{}: {}", path, e)));
+    setup.settings.max_rounds = setup.settings.max_rounds.min(rounds);
+    Ok(setup)
+}
+
+fn write_weights(path: &str, weights: &EvalWeights) -> Result<(), String> {
+    File::create(path)
+        .and_then(|mut file| write!(file, "{}", weights))
+        .map_err(|e| format!("couldn't write {}: {}", path, e))
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        generations: 10,
+        population: 8,
+        games: 4,
+        iterations: 50,
+        rounds: 40,
+        seed: 0,
+        start: EvalWeights::default(),
+        output: "weights.txt".to_owned(),
+        transcripts: Vec::new()
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            options.transcripts.push(arg);
+            continue;
+        }
+        let value = try!(args.next().ok_or(format!("{} needs a value", arg)));
+        let number = || value.parse::<u64>().map_err(|_| format!("{} needs a number, got {}", arg, value));
+        match arg.as_ref() {
+            "--generations" => options.generations = try!(number()),
+            "--population" => options.population = try!(number()) as usize,
+            "--games" => options.games = try!(number()),
+            "--iterations" => options.iterations = try!(number()),
+            "--rounds" => options.rounds = try!(number()),
+            "--seed" => options.seed = try!(number()),
+            "--start" => options.start = try!(EvalWeights::load(&value)),
+            "--output" => options.output = value.clone(),
+            _ => return Err(format!("unknown argument {}", arg))
+        }
+    }
+    if options.transcripts.is_empty() {
+        return Err("usage: warlib-tune [options] <transcript>...".to_owned());
+    }
+    if options.population == 0 {
+        return Err("--population has to be at least 1".to_owned());
+    }
+    Ok(options)
+}
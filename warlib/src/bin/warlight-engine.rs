@@ -0,0 +1,133 @@
+//! Referees a game between two bots on this machine.
+//!
+//!     warlight-engine [options] <transcript> <bot> <bot>
+//!
+//! The map and settings are read from the `setup_map` and `settings` lines of
+//! the transcript, the fodder files under `tests/fodder` will do, or from a
+//! map file, see `warlib::mapfile`. Each bot is a command line, quoted as one
+//! argument, that is started as a child process and spoken to over stdin and
+//! stdout like theaigames.com would, e.g. `"target/debug/warbot --strategy
+//! planner"`. Quotes and backslashes inside it work like in a shell, for
+//! paths with spaces. The first bot given is player1. The game log is
+//! written to the replay file once it is over.
+//!
+//! Options:
+//!
+//!     --seed <n>             seed for the picks, the dice and both bots,
+//!                            random by default
+//!     --timebank <ms>        most a bot's timebank can hold, 10000
+//!     --time-per-move <ms>   added to the timebank after every answer, 500
+//!     --rounds <n>           cap on the rounds played
+//!     --replay <file>        where to write the game log, game.log
+
+extern crate warlib;
+
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process;
+use warlib::engine::{EngineConfig, ProcessPlayer, run_game};
+use warlib::game::load_setup;
+use warlib::state::GameState;
+use warlib::strategy::seeded_rng;
+
+struct Options {
+    seed: u64,
+    config: EngineConfig,
+    rounds: Option<u64>,
+    replay: String,
+    transcript: String,
+    bots: Vec<String>
+}
+
+fn main() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    let setup = match read_setup(&options.transcript, options.rounds) {
+        Ok(setup) => setup,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(2);
+        }
+    };
+    let mut players = Vec::new();
+    // Player2 gets a seed of its own like in `arena::Match`.
+    for (side, command) in options.bots.iter().enumerate() {
+        match ProcessPlayer::start(command, options.seed ^ side as u64) {
+            Ok(player) => players.push(player),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(2);
+            }
+        }
+    }
+    let mut second = players.pop().unwrap();
+    let mut first = players.pop().unwrap();
+
+    println!("seed {}", options.seed);
+    let (result, log) = run_game(&setup, [&mut first, &mut second], &options.config, &mut seeded_rng(options.seed));
+    match result.winner {
+        Some(winner) => println!("player{} won after {} rounds", winner + 1, result.rounds),
+        None => println!("draw after {} rounds", result.rounds)
+    }
+    println!("regions held: {} to {}", result.regions[0], result.regions[1]);
+
+    let written = File::create(&options.replay)
+        .and_then(|mut file| file.write_all(log.join("\n").as_bytes()).and_then(|_| file.write_all(b"\n")));
+    if let Err(e) = written {
+        eprintln!("couldn't write {}: {}", options.replay, e);
+        process::exit(1);
+    }
+    println!("wrote {}", options.replay);
+}
+
+fn read_setup(path: &str, rounds: Option<u64>) -> Result<GameState, String> {
+    let mut contents = String::new();
+    try!(File::open(path).and_then(|mut file| file.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read {}: {}", path, e)));
+    let mut setup = try!(load_setup(&contents).map_err(|e| format!("{}: {}", path, e)));
+    if let Some(rounds) = rounds {
+        setup.settings.max_rounds = setup.settings.max_rounds.min(rounds);
+    }
+    Ok(setup)
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        seed: warlib::default_seed(),
+        config: EngineConfig::default(),
+        rounds: None,
+        replay: "game.log".to_owned(),
+        transcript: String::new(),
+        bots: Vec::new()
+    };
+    let mut positional = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            positional.push(arg);
+            continue;
+        }
+        let value = try!(args.next().ok_or(format!("{} needs a value", arg)));
+        let number = || value.parse::<u64>().map_err(|_| format!("{} needs a number, got {}", arg, value));
+        match arg.as_ref() {
+            "--seed" => options.seed = try!(number()),
+            "--timebank" => options.config.timebank = try!(number()),
+            "--time-per-move" => options.config.time_per_move = try!(number()),
+            "--rounds" => options.rounds = Some(try!(number())),
+            "--replay" => options.replay = value.clone(),
+            _ => return Err(format!("unknown argument {}", arg))
+        }
+    }
+    if positional.len() != 3 {
+        return Err("usage: warlight-engine [options] <transcript> <bot> <bot>".to_owned());
+    }
+    options.transcript = positional.remove(0);
+    options.bots = positional;
+    Ok(options)
+}
@@ -0,0 +1,57 @@
+//! Checks map files and makes them from transcripts, see `warlib::mapfile`.
+//!
+//!     warlight-map check <map>...
+//!     warlight-map convert <transcript>
+//!
+//! `check` reads every map given and says what is wrong with those that
+//! don't hold together, exiting with 1 if any don't. `convert` prints the
+//! map and starting regions of a transcript as a map file.
+
+extern crate warlib;
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process;
+use warlib::game::load_setup;
+use warlib::mapfile::MapFile;
+
+const USAGE: &'static str = "usage: warlight-map check <map>... | warlight-map convert <transcript>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(|command| command.as_ref()) {
+        Some("check") if args.len() > 1 => {
+            let mut broken = 0;
+            for path in args[1..].iter() {
+                match MapFile::load(path) {
+                    Ok(file) => println!("{}: {} regions in {} super regions", path,
+                        file.map.region_ids().len(), file.map.super_region_ids().len()),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        broken += 1;
+                    }
+                }
+            }
+            process::exit(if broken > 0 {1} else {0});
+        },
+        Some("convert") if args.len() == 2 => {
+            let path = &args[1];
+            let mut contents = String::new();
+            let setup = File::open(path).and_then(|mut file| file.read_to_string(&mut contents))
+                .map_err(|e| format!("couldn't read {}: {}", path, e))
+                .and_then(|_| load_setup(&contents).map_err(|e| format!("{}: {}", path, e)));
+            match setup {
+                Ok(setup) => print!("{}", MapFile::from_setup(&setup).to_text()),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(2);
+                }
+            }
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
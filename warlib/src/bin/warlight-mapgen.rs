@@ -0,0 +1,106 @@
+//! Makes random maps, see `warlib::mapgen`.
+//!
+//!     warlight-mapgen [options]
+//!
+//! Prints a map file, or with `--setup` the `setup_map` and `settings
+//! starting_regions` lines the engine would send, which are a transcript as
+//! far as the engine, tournaments and tuning are concerned. Map files start
+//! with the command that makes the same map again, every option spelled out.
+//!
+//! Options:
+//!
+//!     --seed <n>                 seed for the map, random by default
+//!     --regions <n>              42
+//!     --super-regions <n>        8
+//!     --neighbors <n>            regions each region is joined to at most, 4
+//!     --bonus-per-region <x>     bonus for every region of a super region, 0.7
+//!     --bonus-jitter <n>         bonuses are off by up to this much, 1
+//!     --wastelands <n>           4
+//!     --wastelands-anywhere      not only between super regions
+//!     --setup                    print setup_map lines instead of a map file
+//!     --output <file>            write the map there rather than to stdout
+
+extern crate warlib;
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::process;
+use warlib::mapgen::{MapGenConfig, WastelandPlacement, generate};
+use warlib::strategy::seeded_rng;
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut config = MapGenConfig::default();
+    let mut seed = warlib::default_seed();
+    let mut setup = false;
+    let mut output = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "--setup" => {
+                setup = true;
+                continue;
+            },
+            "--wastelands-anywhere" => {
+                config.wasteland_placement = WastelandPlacement::Anywhere;
+                continue;
+            },
+            _ => {}
+        }
+        let value = try!(args.next().ok_or(format!("{} needs a value", arg)));
+        let number = || value.parse::<u64>().map_err(|_| format!("{} needs a number, got {}", arg, value));
+        match arg.as_ref() {
+            "--seed" => seed = try!(number()),
+            "--regions" => config.regions = try!(number()),
+            "--super-regions" => config.super_regions = try!(number()),
+            "--neighbors" => config.neighbors = try!(number()) as usize,
+            "--bonus-per-region" => config.bonus_per_region = try!(value.parse()
+                .map_err(|_| format!("--bonus-per-region needs a number, got {}", value))),
+            "--bonus-jitter" => config.bonus_jitter = try!(number()),
+            "--wastelands" => config.wastelands = try!(number()),
+            "--output" => output = Some(value.clone()),
+            _ => return Err(format!("unknown argument {}", arg))
+        }
+    }
+
+    let file = try!(generate(&config, &mut seeded_rng(seed)));
+    let text = if setup {
+        file.setup_lines().join("\n") + "\n"
+    } else {
+        format!("# {}\n{}", command(&config, seed), file.to_text())
+    };
+    match output {
+        Some(path) => File::create(&path)
+            .and_then(|mut out| out.write_all(text.as_bytes()))
+            .map_err(|e| format!("couldn't write {}: {}", path, e)),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+/// The command line that generates the map for `config` and `seed`.
+fn command(config: &MapGenConfig, seed: u64) -> String {
+    let mut command = format!(
+        "warlight-mapgen --seed {} --regions {} --super-regions {} --neighbors {} --bonus-per-region {} --bonus-jitter {} --wastelands {}",
+        seed,
+        config.regions,
+        config.super_regions,
+        config.neighbors,
+        config.bonus_per_region,
+        config.bonus_jitter,
+        config.wastelands
+    );
+    if config.wasteland_placement == WastelandPlacement::Anywhere {
+        command.push_str(" --wastelands-anywhere");
+    }
+    command
+}
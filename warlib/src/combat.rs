@@ -0,0 +1,121 @@
+//! Warlight 2 combat.
+//!
+//! Each attacking army has a 60% chance to destroy a defending army and each
+//! defending army a 70% chance to destroy an attacking army, all rolled at
+//! once. The region falls when every defender is destroyed and at least one
+//! attacker survives, otherwise the surviving attackers stay home.
+
+use rand::Rng;
+
+pub const ATTACKER_KILL_CHANCE: f64 = 0.6;
+pub const DEFENDER_KILL_CHANCE: f64 = 0.7;
+
+/// Armies lost on each side of a single attack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outcome {
+    pub attackers_lost: u64,
+    pub defenders_lost: u64
+}
+
+impl Outcome {
+    /// Whether the attack took the region given how many armies it started
+    /// with on each side.
+    pub fn captured(&self, attackers: u64, defenders: u64) -> bool {
+        self.defenders_lost >= defenders && self.attackers_lost < attackers
+    }
+}
+
+/// Rolls a single attack.
+pub fn simulate<R: Rng>(attackers: u64, defenders: u64, rng: &mut R) -> Outcome {
+    let mut defenders_lost = 0;
+    for _ in 0..attackers {
+        if rng.gen::<f64>() < ATTACKER_KILL_CHANCE {
+            defenders_lost += 1;
+        }
+    }
+    let mut attackers_lost = 0;
+    for _ in 0..defenders {
+        if rng.gen::<f64>() < DEFENDER_KILL_CHANCE {
+            attackers_lost += 1;
+        }
+    }
+    Outcome {
+        attackers_lost: attackers_lost.min(attackers),
+        defenders_lost: defenders_lost.min(defenders)
+    }
+}
+
+/// The average attack, rounded to whole armies.
+pub fn expected(attackers: u64, defenders: u64) -> Outcome {
+    let defenders_lost = (attackers as f64 * ATTACKER_KILL_CHANCE).round() as u64;
+    let attackers_lost = (defenders as f64 * DEFENDER_KILL_CHANCE).round() as u64;
+    Outcome {
+        attackers_lost: attackers_lost.min(attackers),
+        defenders_lost: defenders_lost.min(defenders)
+    }
+}
+
+/// Fewest attackers that take a region held by `defenders` on average.
+pub fn armies_needed(defenders: u64) -> u64 {
+    let mut attackers = 1;
+    while !expected(attackers, defenders).captured(attackers, defenders) {
+        attackers += 1;
+    }
+    attackers
+}
+
+/// Fewest defenders that hold against `attackers` on average.
+pub fn defenders_needed(attackers: u64) -> u64 {
+    let mut defenders = 1;
+    while expected(attackers, defenders).captured(attackers, defenders) {
+        defenders += 1;
+    }
+    defenders
+}
+
+/// Several attacks on the same region one after the other, each wave facing
+/// what the ones before left behind. Returns the defenders left after the
+/// waves and the index of the wave that took the region, if one did. Waves
+/// after that one would only be transfers and change nothing.
+pub fn expected_waves(defenders: u64, waves: &[u64]) -> (u64, Option<usize>) {
+    let mut left = defenders;
+    for (index, attackers) in waves.iter().enumerate() {
+        let outcome = expected(*attackers, left);
+        if outcome.captured(*attackers, left) {
+            return (0, Some(index));
+        }
+        left -= outcome.defenders_lost;
+    }
+    (left, None)
+}
+
+#[test]
+fn expected_attacks() {
+    assert_eq!(armies_needed(1), 2);
+    assert_eq!(armies_needed(2), 3);
+    assert_eq!(armies_needed(6), 10);
+    assert!(expected(3, 2).captured(3, 2));
+    assert!(!expected(2, 2).captured(2, 2));
+    assert_eq!(defenders_needed(0), 1);
+    assert_eq!(defenders_needed(3), 3);
+    assert_eq!(defenders_needed(5), 4);
+}
+
+#[test]
+fn simulated_losses_are_bounded() {
+    let mut rng = ::rand::XorShiftRng::new_unseeded();
+    for _ in 0..100 {
+        let outcome = simulate(3, 5, &mut rng);
+        assert!(outcome.attackers_lost <= 3);
+        assert!(outcome.defenders_lost <= 5);
+    }
+}
+
+#[test]
+fn waves_wear_down_defenders() {
+    // 4 attackers kill 2 of 5 defenders and lose all four.
+    assert_eq!(expected_waves(5, &[4]), (3, None));
+    // The 3 left fall to the next 5.
+    assert_eq!(expected_waves(5, &[4, 5, 9]), (0, Some(1)));
+    assert_eq!(expected_waves(5, &[]), (5, None));
+}
@@ -0,0 +1,300 @@
+//! Planning which super regions to take next.
+//!
+//! Every super region we don't hold yet is costed by the armies it takes to
+//! beat whatever is left in it and by how many rounds that takes, counted as
+//! the distance from our nearest region to the furthest region still to
+//! take. Wastelands cost extra on top of their armies since they can only be
+//! taken by one big attack. Super regions out of reach within `horizon`
+//! rounds, or that pay nothing, aren't planned for.
+//!
+//! `orders()` turns the plans into this round's moves: the targets next to
+//! our regions are attacked, cheapest first, by `assault()`. A target one
+//! neighbour can't take alone is attacked by several in waves, the engine
+//! resolves them one after the other so each wave faces what the ones
+//! before left, and the income covers whatever the waves are short of.
+//! Armies reserved for defence are never spare. What is left is placed
+//! towards the best plan.
+
+use combat::{armies_needed, expected_waves};
+use map::{GameMap, OwnerValue};
+use moves::Move;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct ConquestOptions {
+    /// Rounds ahead a super region has to be finished within.
+    pub horizon: u64,
+    /// Armies added to the cost for every wasteland still to take.
+    pub wasteland_penalty: u64,
+    /// Super regions planned for at once.
+    pub max_plans: usize
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuperRegionPlan {
+    pub super_region_id: u64,
+    /// The regions of the super region we don't hold, cheapest first.
+    pub targets: Vec<u64>,
+    /// Armies needed to take all of `targets`.
+    pub cost: u64,
+    /// Rounds before the last target can be reached.
+    pub rounds: u64,
+    /// Bonus per army of cost, higher is better.
+    pub score: f64
+}
+
+impl Default for ConquestOptions {
+    fn default() -> ConquestOptions {
+        ConquestOptions {
+            horizon: 3,
+            wasteland_penalty: 2,
+            max_plans: 2
+        }
+    }
+}
+
+/// The super regions worth going for, best first.
+pub fn plan(map: &GameMap, options: &ConquestOptions) -> Vec<SuperRegionPlan> {
+    let ours: Vec<u64> = map.owned_by(OwnerValue::Ally).iter().map(|region| region.id).collect();
+    if ours.is_empty() {
+        return Vec::new();
+    }
+    let distances = map.distances(&ours);
+
+    let mut plans = Vec::new();
+    for id in map.super_region_ids() {
+        let super_region = map.super_region(id).unwrap();
+        if super_region.value == 0 || map.owns_super_region(OwnerValue::Ally, id) {
+            continue;
+        }
+        let mut targets: Vec<u64> = super_region.region_ids.iter()
+            .filter(|region_id| map.region(**region_id).unwrap().owner != OwnerValue::Ally)
+            .cloned()
+            .collect();
+        targets.sort_by_key(|region_id| (map.region(*region_id).unwrap().armies, *region_id));
+
+        let mut cost = 0;
+        let mut rounds = 0;
+        for target in targets.iter() {
+            let region = map.region(*target).unwrap();
+            cost += armies_needed(region.armies);
+            if region.wasteland {
+                cost += options.wasteland_penalty;
+            }
+            rounds = rounds.max(distances.get(target).cloned().unwrap_or(u64::max_value()));
+        }
+        if rounds > options.horizon {
+            continue;
+        }
+        plans.push(SuperRegionPlan {
+            super_region_id: id,
+            targets: targets,
+            cost: cost,
+            rounds: rounds,
+            score: super_region.value as f64 / cost.max(1) as f64
+        });
+    }
+    plans.sort_by(|a, b| {
+        b.score.partial_cmp(&a.score).unwrap()
+            .then(a.rounds.cmp(&b.rounds))
+            .then(a.super_region_id.cmp(&b.super_region_id))
+    });
+    plans.truncate(options.max_plans);
+    plans
+}
+
+/// Placements and attacks for this round working towards `plans`, spending
+/// at most `income`. `reserved` holds the armies regions have to keep at
+/// home, placements already recorded on `map` included. Placements come
+/// first in the result.
+pub fn orders(map: &GameMap, plans: &[SuperRegionPlan], income: u64, reserved: &HashMap<u64, u64>) -> Vec<Move> {
+    let mut left = income;
+    let mut spare: HashMap<u64, u64> = map.owned_by(OwnerValue::Ally).iter()
+        .map(|region| {
+            let keep = reserved.get(&region.id).cloned().unwrap_or(1).max(1);
+            let armies = region.armies + map.placed_armies(region.id);
+            (region.id, armies.saturating_sub(keep + map.sent_armies(region.id)))
+        })
+        .collect();
+    let mut placed: Vec<(u64, u64)> = Vec::new();
+    let mut attacks = Vec::new();
+
+    for plan in plans {
+        for target in plan.targets.iter() {
+            let (waves, extra) = match assault(map, &spare, *target, left) {
+                Some(assault) => assault,
+                None => continue
+            };
+            if extra > 0 {
+                place(&mut placed, waves[0].0, extra);
+                left -= extra;
+                *spare.get_mut(&waves[0].0).unwrap() += extra;
+            }
+            for (source, armies) in waves {
+                *spare.get_mut(&source).unwrap() -= armies;
+                attacks.push(Move::AttackTransfer(source, *target, armies));
+            }
+        }
+    }
+
+    if left > 0 {
+        let staging = attacks.first()
+            .and_then(|movement| match *movement {
+                Move::AttackTransfer(source, _, _) => Some(source),
+                _ => None
+            })
+            .or_else(|| staging_region(map, plans));
+        if let Some(id) = staging {
+            place(&mut placed, id, left);
+        }
+    }
+
+    let mut moves: Vec<Move> = placed.into_iter().map(|(id, armies)| Move::PlaceArmies(id, armies)).collect();
+    moves.extend(attacks);
+    moves
+}
+
+/// The waves that take `target`, as `(source, armies)` in the order they
+/// have to be sent, and the armies to place on the first source for them.
+/// `spare` holds what each of our regions can attack with.
+///
+/// The neighbours with the most to spare go first, as few of them as will
+/// do, and only when all of them together fall short is anything placed, up
+/// to `income`. The last wave only takes what it needs to finish the job.
+pub fn assault(map: &GameMap, spare: &HashMap<u64, u64>, target: u64, income: u64) -> Option<(Vec<(u64, u64)>, u64)> {
+    let defenders = map.region(target).unwrap().armies;
+    let mut sources: Vec<(u64, u64)> = map.region(target).unwrap().neighbor_ids.iter()
+        .filter_map(|neighbor| spare.get(neighbor).map(|armies| (*neighbor, *armies)))
+        .collect();
+    sources.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    if sources.is_empty() {
+        return None;
+    }
+    // Neighbours with nothing to spare can still be placed on, but only the
+    // first of them is worth it.
+    let usable = sources.iter().filter(|&&(_, armies)| armies > 0).count();
+    sources.truncate(usable.max(1));
+
+    for extra in 0..income + 1 {
+        let mut armies: Vec<u64> = sources.iter().map(|&(_, armies)| armies).collect();
+        armies[0] += extra;
+        if let (_, Some(last)) = expected_waves(defenders, &armies) {
+            let (left, _) = expected_waves(defenders, &armies[..last]);
+            armies[last] = armies[last].min(armies_needed(left));
+            let waves = sources.iter().zip(armies.into_iter()).take(last + 1)
+                .map(|(&(id, _), armies)| (id, armies))
+                .collect();
+            return Some((waves, extra));
+        }
+    }
+    None
+}
+
+/// Our region closest to the best plan's targets, the strongest if several
+/// are as close. Falls back to our strongest border region.
+fn staging_region(map: &GameMap, plans: &[SuperRegionPlan]) -> Option<u64> {
+    let ours = map.owned_by(OwnerValue::Ally);
+    let distances = match plans.first() {
+        Some(plan) => map.distances(&plan.targets),
+        None => HashMap::new()
+    };
+    ours.iter()
+        .filter(|region| map.is_border(OwnerValue::Ally, region.id))
+        .min_by(|a, b| {
+            let distance = |id| distances.get(&id).cloned().unwrap_or(u64::max_value());
+            distance(a.id).cmp(&distance(b.id))
+                .then(b.armies.cmp(&a.armies))
+                .then(a.id.cmp(&b.id))
+        })
+        .map(|region| region.id)
+}
+
+fn place(placed: &mut Vec<(u64, u64)>, id: u64, armies: u64) {
+    match placed.iter_mut().find(|entry| entry.0 == id) {
+        Some(entry) => entry.1 += armies,
+        None => placed.push((id, armies))
+    }
+}
+
+#[cfg(test)]
+fn test_map() -> GameMap {
+    // Super region 1 is 1 - 2 - 3 worth 3, super region 2 is 4 - 5 worth 3
+    // with 5 a wasteland, super region 3 is 6 worth 1 and far away. They
+    // sit in a line, we hold 1 and 4.
+    let mut map = GameMap::new();
+    map.add_super_region(1, 3);
+    map.add_super_region(2, 3);
+    map.add_super_region(3, 1);
+    for (id, super_region) in vec![(1, 1), (2, 1), (3, 1), (4, 2), (5, 2), (6, 3)] {
+        map.add_region(id, super_region);
+    }
+    for id in 1..6 {
+        map.add_region_neighbors(id, vec![id + 1]);
+    }
+    map.upgrade_to_wasteland(5);
+    map.update_map(1, OwnerValue::Ally, 4);
+    map.update_map(4, OwnerValue::Ally, 2);
+    map
+}
+
+#[test]
+fn plans_prefer_cheap_bonuses() {
+    let mut options = ConquestOptions::default();
+    options.max_plans = 5;
+    let plans = plan(&test_map(), &options);
+    let ids: Vec<u64> = plans.iter().map(|plan| plan.super_region_id).collect();
+    assert_eq!(ids, vec![1, 3, 2]);
+    assert_eq!(plans[0].targets, vec![2, 3]);
+    let costs: Vec<u64> = plans.iter().map(|plan| plan.cost).collect();
+    assert_eq!(costs, vec![6, 3, 12]);
+}
+
+#[test]
+fn far_super_regions_are_left_out() {
+    let mut options = ConquestOptions::default();
+    options.horizon = 1;
+    options.max_plans = 5;
+    let mut map = test_map();
+    map.update_map(4, OwnerValue::Neutral, 2);
+    let ids: Vec<u64> = plan(&map, &options).iter().map(|plan| plan.super_region_id).collect();
+    assert_eq!(ids, vec![]);
+    options.horizon = 2;
+    let ids: Vec<u64> = plan(&map, &options).iter().map(|plan| plan.super_region_id).collect();
+    assert_eq!(ids, vec![1]);
+}
+
+#[test]
+fn orders_cover_the_shortfall() {
+    let map = test_map();
+    let plans = plan(&map, &ConquestOptions::default());
+    let moves = orders(&map, &plans, 5, &HashMap::new());
+    assert_eq!(moves, vec![
+        Move::PlaceArmies(4, 2),
+        Move::PlaceArmies(1, 3),
+        Move::AttackTransfer(1, 2, 3),
+        Move::AttackTransfer(4, 3, 3)
+    ]);
+}
+
+#[test]
+fn neighbours_attack_together() {
+    // 2 holds 5 between 1 and 3, which have 5 to spare each.
+    let mut map = GameMap::new();
+    map.add_super_region(1, 2);
+    for id in 1..4 {
+        map.add_region(id, 1);
+    }
+    map.add_region_neighbors(2, vec![1, 3]);
+    map.update_map(1, OwnerValue::Ally, 6);
+    map.update_map(2, OwnerValue::Neutral, 5);
+    map.update_map(3, OwnerValue::Ally, 6);
+    let spare: HashMap<u64, u64> = vec![(1, 5), (3, 5)].into_iter().collect();
+
+    // One wave of 5 leaves 2, the second only needs 3 of its 5 for that.
+    assert_eq!(assault(&map, &spare, 2, 0), Some((vec![(1, 5), (3, 3)], 0)));
+
+    // A single neighbour needs 3 more to do it alone.
+    let spare: HashMap<u64, u64> = vec![(1, 5), (3, 0)].into_iter().collect();
+    assert_eq!(assault(&map, &spare, 2, 5), Some((vec![(1, 8)], 3)));
+    assert_eq!(assault(&map, &spare, 2, 2), None);
+}
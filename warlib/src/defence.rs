@@ -0,0 +1,160 @@
+//! Holding on to what we have.
+//!
+//! Each of our regions next to the enemy is threatened by every army the
+//! enemy could attack it with next round: what sits in its enemy neighbours
+//! bar the one that has to stay behind in each, plus a share of their income
+//! in case they place it there. The combat calculator gives the armies that
+//! hold against that. Losing a region of a super region we hold costs the
+//! bonus too, so those regions have more at stake.
+//!
+//! `defend()` places for the threats worth more per army than a threshold,
+//! which lets a planner weigh them against what its attacks would earn.
+
+use combat::defenders_needed;
+use map::{GameMap, OwnerValue};
+use moves::Move;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct DefenceOptions {
+    /// How much a super region bonus we would lose counts, per army of
+    /// bonus. A plain region is worth 1.
+    pub bonus_weight: f64,
+    /// Share of the enemy's income assumed to be placed next to every
+    /// threatened region.
+    pub income_share: f64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Threat {
+    pub region_id: u64,
+    /// Armies the enemy could attack with.
+    pub attackers: u64,
+    /// Armies the region needs to hold.
+    pub defenders: u64,
+    /// Armies that have to be placed on top of what is there.
+    pub shortfall: u64,
+    /// What losing the region would cost us.
+    pub at_stake: f64
+}
+
+impl Default for DefenceOptions {
+    fn default() -> DefenceOptions {
+        DefenceOptions {
+            bonus_weight: 1.0,
+            income_share: 1.0
+        }
+    }
+}
+
+impl Threat {
+    /// Value saved per army placed, regions that already hold come first.
+    pub fn score(&self) -> f64 {
+        if self.shortfall == 0 {
+            ::std::f64::INFINITY
+        } else {
+            self.at_stake / self.shortfall as f64
+        }
+    }
+}
+
+/// Every region of ours the enemy could attack next round, the best to
+/// defend first.
+pub fn threats(map: &GameMap, options: &DefenceOptions) -> Vec<Threat> {
+    let enemy_income = (map.income(OwnerValue::Enemy) as f64 * options.income_share).round() as u64;
+    let mut threats: Vec<Threat> = map.owned_by(OwnerValue::Ally).iter().filter_map(|region| {
+        let adjacent: Vec<u64> = region.neighbor_ids.iter()
+            .map(|neighbor| map.region(*neighbor).unwrap())
+            .filter(|neighbor| neighbor.owner == OwnerValue::Enemy)
+            .map(|neighbor| neighbor.armies.saturating_sub(1))
+            .collect();
+        if adjacent.is_empty() {
+            return None;
+        }
+        let attackers = adjacent.iter().sum::<u64>() + enemy_income;
+        let defenders = defenders_needed(attackers);
+        let armies = region.armies + map.placed_armies(region.id);
+        let mut at_stake = 1.0;
+        if map.owns_super_region(OwnerValue::Ally, region.super_region_id) {
+            at_stake += options.bonus_weight * map.super_region(region.super_region_id).unwrap().value as f64;
+        }
+        Some(Threat {
+            region_id: region.id,
+            attackers: attackers,
+            defenders: defenders,
+            shortfall: defenders.saturating_sub(armies),
+            at_stake: at_stake
+        })
+    }).collect();
+    threats.sort_by(|a, b| b.score().partial_cmp(&a.score()).unwrap().then(a.region_id.cmp(&b.region_id)));
+    threats
+}
+
+/// Placements that hold the threatened regions scoring at least `threshold`,
+/// spending at most `income`. Also returns the armies each held region has
+/// to keep at home, placements included. Regions that can't be held with
+/// what is left aren't placed on at all.
+pub fn defend(threats: &[Threat], income: u64, threshold: f64) -> (Vec<Move>, HashMap<u64, u64>) {
+    let mut left = income;
+    let mut moves = Vec::new();
+    let mut reserved = HashMap::new();
+    for threat in threats {
+        if threat.score() < threshold || threat.shortfall > left {
+            continue;
+        }
+        if threat.shortfall > 0 {
+            moves.push(Move::PlaceArmies(threat.region_id, threat.shortfall));
+            left -= threat.shortfall;
+        }
+        reserved.insert(threat.region_id, threat.defenders);
+    }
+    (moves, reserved)
+}
+
+#[cfg(test)]
+fn test_map() -> GameMap {
+    // We hold super region 1, regions 1 and 2 worth 3, and region 3 of super
+    // region 2. The enemy holds 4 next to 2 and 5 next to 3.
+    let mut map = GameMap::new();
+    map.add_super_region(1, 3);
+    map.add_super_region(2, 1);
+    for (id, super_region) in vec![(1, 1), (2, 1), (3, 2), (4, 2), (5, 2)] {
+        map.add_region(id, super_region);
+    }
+    map.add_region_neighbors(1, vec![2, 3]);
+    map.add_region_neighbors(2, vec![4]);
+    map.add_region_neighbors(3, vec![5]);
+    for id in 1..4 {
+        map.update_map(id, OwnerValue::Ally, 2);
+    }
+    map.update_map(4, OwnerValue::Enemy, 4);
+    map.update_map(5, OwnerValue::Enemy, 4);
+    map
+}
+
+#[test]
+fn bonus_regions_come_first() {
+    let mut options = DefenceOptions::default();
+    options.income_share = 0.0;
+    let threats = threats(&test_map(), &options);
+    let ids: Vec<u64> = threats.iter().map(|threat| threat.region_id).collect();
+    assert_eq!(ids, vec![2, 3]);
+    assert_eq!(threats[0].attackers, 3);
+    assert_eq!(threats[0].shortfall, 1);
+    assert_eq!(threats[0].at_stake, 4.0);
+    assert_eq!(threats[1].at_stake, 1.0);
+}
+
+#[test]
+fn defence_respects_threshold_and_income() {
+    let mut options = DefenceOptions::default();
+    options.income_share = 0.0;
+    let threats = threats(&test_map(), &options);
+    let (moves, reserved) = defend(&threats, 5, 2.0);
+    assert_eq!(moves, vec![Move::PlaceArmies(2, 1)]);
+    assert_eq!(reserved.get(&2), Some(&3));
+    assert_eq!(reserved.get(&3), None);
+
+    let (moves, _) = defend(&threats, 1, 0.0);
+    assert_eq!(moves, vec![Move::PlaceArmies(2, 1)]);
+}
@@ -0,0 +1,592 @@
+//! A stand-in for the theaigames.com Warlight 2 engine.
+//!
+//! `run_game()` referees a game between two `Player`s the way the real engine
+//! would: every player gets the settings and `setup_map` lines, picks its
+//! starting regions in snake order, and then every round is sent its income,
+//! an `update_map` with only the regions it can see, the opponent moves it
+//! could see happen, and is asked for placements and attack/transfer moves.
+//! Rounds are played out with `rules::resolve_round()`, moves the rules don't
+//! allow are skipped like the real engine skips them.
+//!
+//! A player sees its own regions and their neighbours. Of the opponent's
+//! moves it is shown the ones touching a region it could see before or
+//! after the round.
+//!
+//! Every player has a timebank. Each request may take at most what is in
+//! it, the time taken comes off it and `time_per_move` is added back
+//! afterwards, up to the full `timebank`. A player that doesn't answer in
+//! time makes no moves, or gets the first region on offer when picking.
+//!
+//! The game is logged line by line for replays:
+//!
+//! ```text
+//! round <n>                     rounds start, 0 is the picking phase
+//! <player> < <line>             a line sent to the player
+//! <player> > <line>             the player's answer
+//! <player> timed out            no answer in time
+//! moves <move> <move> ...       the moves that happened, in order
+//! map <id> <owner> <armies> ... every region after the picks or round
+//! result <winner> <rounds>      the winner is `none` for a draw
+//! ```
+
+use game::{self, GameResult};
+use map::{GameMap, OwnerValue};
+use moves::Move;
+use parser::{Message, OpponentMoveValue, parse};
+use picks::first_player_picks;
+use rand::{Rng, XorShiftRng};
+use rules::resolve_round;
+use state::GameState;
+use SEED_VAR;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError, channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Someone taking part in a game, a bot in another process or in this one.
+pub trait Player {
+    /// Passes a line that doesn't need an answer.
+    fn send(&mut self, line: &str);
+    /// Passes a line and waits at most `timeout` for the answer, `None` if
+    /// it didn't come or the player has gone away.
+    fn request(&mut self, line: &str, timeout: Duration) -> Option<String>;
+}
+
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    /// Most a player's timebank can hold, in milliseconds.
+    pub timebank: u64,
+    /// Added to a player's timebank after every answer, in milliseconds.
+    pub time_per_move: u64
+}
+
+/// A bot running as a child process, spoken to over its stdin and stdout.
+/// Whatever it writes to stderr is thrown away.
+pub struct ProcessPlayer {
+    child: Child,
+    input: ChildStdin,
+    // `None` once its stdout is closed.
+    output: Receiver<Option<String>>,
+    gone: bool
+}
+
+struct Referee<'a> {
+    players: [&'a mut Player; 2],
+    config: EngineConfig,
+    banks: [u64; 2],
+    log: Vec<String>
+}
+
+impl Default for EngineConfig {
+    fn default() -> EngineConfig {
+        EngineConfig {
+            timebank: 10000,
+            time_per_move: 500
+        }
+    }
+}
+
+impl ProcessPlayer {
+    /// Starts `command`, split into the program and its arguments by
+    /// `split_command()`, with `seed` in its `WARBOT_SEED` so bots that read
+    /// it play the same game again given the same seed.
+    pub fn start(command: &str, seed: u64) -> Result<ProcessPlayer, String> {
+        let parts = try!(split_command(command));
+        if parts.is_empty() {
+            return Err("empty bot command".to_owned());
+        }
+        let mut child = try!(Command::new(&parts[0])
+            .args(&parts[1..])
+            .env(SEED_VAR, seed.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("couldn't start '{}': {}", command, e)));
+        let input = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Reading happens on its own thread so requests can time out.
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => if tx.send(Some(line)).is_err() {
+                        return;
+                    },
+                    Err(_) => break
+                }
+            }
+            tx.send(None).unwrap_or(());
+        });
+
+        Ok(ProcessPlayer {
+            child: child,
+            input: input,
+            output: rx,
+            gone: false
+        })
+    }
+}
+
+impl Player for ProcessPlayer {
+    fn send(&mut self, line: &str) {
+        if self.gone {
+            return;
+        }
+        if let Err(e) = writeln!(self.input, "{}", line).and_then(|_| self.input.flush()) {
+            warn!("bot stopped reading: {}", e);
+            self.gone = true;
+        }
+    }
+
+    fn request(&mut self, line: &str, timeout: Duration) -> Option<String> {
+        // Anything already waiting answered an earlier request too late.
+        loop {
+            match self.output.try_recv() {
+                Ok(Some(late)) => warn!("dropping late answer '{}'", late),
+                Ok(None) | Err(TryRecvError::Disconnected) => self.gone = true,
+                Err(TryRecvError::Empty) => break
+            }
+            if self.gone {
+                break;
+            }
+        }
+        self.send(line);
+        if self.gone {
+            return None;
+        }
+        match self.output.recv_timeout(timeout) {
+            Ok(Some(answer)) => Some(answer),
+            Ok(None) | Err(RecvTimeoutError::Disconnected) => {
+                self.gone = true;
+                None
+            },
+            Err(RecvTimeoutError::Timeout) => None
+        }
+    }
+}
+
+impl Drop for ProcessPlayer {
+    fn drop(&mut self) {
+        self.child.kill().unwrap_or(());
+        self.child.wait().ok();
+    }
+}
+
+impl<'a> Referee<'a> {
+    fn send(&mut self, player: usize, line: &str) {
+        self.log.push(format!("{} < {}", player_name(player), line));
+        self.players[player].send(line);
+    }
+
+    fn request(&mut self, player: usize, line: &str) -> Option<String> {
+        self.log.push(format!("{} < {}", player_name(player), line));
+        let started = Instant::now();
+        let answer = self.players[player].request(line, Duration::from_millis(self.banks[player]));
+        let used = millis(started.elapsed());
+        self.banks[player] = (self.banks[player].saturating_sub(used) + self.config.time_per_move).min(self.config.timebank);
+        match answer {
+            Some(ref answer) => self.log.push(format!("{} > {}", player_name(player), answer)),
+            None => self.log.push(format!("{} timed out", player_name(player)))
+        }
+        answer
+    }
+
+    /// Asks `player` for its moves of one kind, `"place_armies"` or
+    /// `"attack/transfer"`. Moves of the other kind are dropped.
+    fn moves(&mut self, player: usize, kind: &str) -> Vec<Move> {
+        let line = format!("go {} {}", kind, self.banks[player]);
+        let answer = self.request(player, &line).unwrap_or(String::new());
+        let placing = kind == "place_armies";
+        parse_moves(&answer, &player_name(player)).into_iter()
+            .filter(|movement| match *movement {
+                Move::PlaceArmies(_, _) => placing,
+                Move::AttackTransfer(_, _, _) => !placing
+            })
+            .collect()
+    }
+
+    /// Hands out the starting regions in snake order, a random player
+    /// picking first, and returns what each player took.
+    fn pick_regions(&mut self, map: &mut GameMap, mut offered: Vec<u64>, amount: usize, rng: &mut XorShiftRng) -> [Vec<u64>; 2] {
+        let mut picks = [Vec::new(), Vec::new()];
+        let first = if rng.gen() {0} else {1};
+        let mut index = 0;
+        while index < 2 * amount && !offered.is_empty() {
+            let player = if first_player_picks(index) {first} else {1 - first};
+            let line = format!("pick_starting_region {} {}", self.banks[player], join(&offered));
+            let choice = match self.request(player, &line).and_then(|answer| answer.trim().parse().ok()) {
+                Some(id) if offered.contains(&id) => id,
+                _ => offered[0]
+            };
+            offered.retain(|id| *id != choice);
+            let armies = map.region(choice).unwrap().armies;
+            map.update_map(choice, player_owner(player), armies);
+            picks[player].push(choice);
+            index += 1;
+        }
+        picks
+    }
+}
+
+/// Plays a game from `setup` to the end, returning the result and the log
+/// of everything that happened. `players[0]` is `player1`, who is `Ally` on
+/// the real map.
+pub fn run_game(setup: &GameState, players: [&mut Player; 2], config: &EngineConfig, rng: &mut XorShiftRng) -> (GameResult, Vec<String>) {
+    let mut referee = Referee {
+        players: players,
+        config: config.clone(),
+        banks: [config.timebank, config.timebank],
+        log: vec!["round 0".to_owned()]
+    };
+    let mut map = setup.map.clone();
+
+    let offered = game::offered_regions(setup, rng);
+    for player in 0..2 {
+        for line in setup_lines(setup, player, &offered, config) {
+            referee.send(player, &line);
+        }
+    }
+    let picks = referee.pick_regions(&mut map, offered, setup.settings.starting_pick_amount as usize, rng);
+    for player in 0..2 {
+        if !picks[1 - player].is_empty() {
+            let line = format!("setup_map opponent_starting_regions {}", join(&picks[1 - player]));
+            referee.send(player, &line);
+        }
+    }
+    referee.log.push(map_line(&map));
+
+    let mut seen = [Vec::new(), Vec::new()];
+    let mut round = 0;
+    while round < setup.settings.max_rounds && !game::is_over(&map) {
+        round += 1;
+        referee.log.push(format!("round {}", round));
+        let mut moves = [Vec::new(), Vec::new()];
+        for player in 0..2 {
+            referee.send(player, &format!("settings starting_armies {}", map.income(player_owner(player))));
+            referee.send(player, &update_map(&map, player));
+            referee.send(player, &opponent_moves(&seen[player]));
+            moves[player] = referee.moves(player, "place_armies");
+            let attacks = referee.moves(player, "attack/transfer");
+            moves[player].extend(attacks);
+        }
+
+        let before = [visible(&map, 0), visible(&map, 1)];
+        let executed = resolve_round(&mut map, &moves[0], &moves[1], rng);
+        for player in 0..2 {
+            let after = visible(&map, player);
+            seen[player] = executed.iter()
+                .filter(|&&(owner, _)| owner != player_owner(player))
+                .filter(|&&(_, ref movement)| touched(movement).iter().any(|id| {
+                    before[player].contains(id) || after.contains(id)
+                }))
+                .cloned()
+                .collect();
+        }
+        let mut line = "moves".to_owned();
+        for &(owner, ref movement) in executed.iter() {
+            line.push_str(&format!(" {}", movement.to_command(&owner_name(owner))));
+        }
+        referee.log.push(line);
+        referee.log.push(map_line(&map));
+    }
+
+    let result = game::outcome(&map, round);
+    let winner = result.winner.map_or("none".to_owned(), player_name);
+    referee.log.push(format!("result {} {}", winner, round));
+    (result, referee.log)
+}
+
+/// Splits a command line into words the way a shell would, so paths with
+/// spaces can be quoted: whitespace separates words except inside single or
+/// double quotes, and a backslash outside single quotes takes the next
+/// character as it is.
+pub fn split_command(command: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // Whether there is a word at all, `''` is an empty one.
+    let mut started = false;
+    let mut quote = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('\''), _) => word.push(c),
+            (_, '\\') => match chars.next() {
+                Some(escaped) => {
+                    word.push(escaped);
+                    started = true;
+                },
+                None => return Err(format!("'{}' ends in a backslash", command))
+            },
+            (Some(_), _) => word.push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                started = true;
+            },
+            (None, _) if c.is_whitespace() => if started {
+                words.push(word);
+                word = String::new();
+                started = false;
+            },
+            (None, _) => {
+                word.push(c);
+                started = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("unfinished quote in '{}'", command));
+    }
+    if started {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// The `setup_map` lines describing `map`, without the opponent's starting
+/// regions.
+pub fn map_lines(map: &GameMap) -> Vec<String> {
+    let mut lines = Vec::new();
+    let super_regions: Vec<String> = map.super_region_ids().iter()
+        .map(|id| format!("{} {}", id, map.super_region(*id).unwrap().value))
+        .collect();
+    lines.push(format!("setup_map super_regions {}", super_regions.join(" ")));
+    let regions: Vec<String> = map.region_ids().iter()
+        .map(|id| format!("{} {}", id, map.region(*id).unwrap().super_region_id))
+        .collect();
+    lines.push(format!("setup_map regions {}", regions.join(" ")));
+
+    // Every border once, from the region with the lower id.
+    let mut neighbors = Vec::new();
+    for id in map.region_ids() {
+        let mut higher: Vec<u64> = map.region(id).unwrap().neighbor_ids.iter()
+            .cloned()
+            .filter(|neighbor| *neighbor > id)
+            .collect();
+        higher.sort();
+        higher.dedup();
+        if !higher.is_empty() {
+            let higher: Vec<String> = higher.iter().map(|neighbor| neighbor.to_string()).collect();
+            neighbors.push(format!("{} {}", id, higher.join(",")));
+        }
+    }
+    if !neighbors.is_empty() {
+        lines.push(format!("setup_map neighbors {}", neighbors.join(" ")));
+    }
+
+    let wastelands: Vec<u64> = map.region_ids().into_iter()
+        .filter(|id| map.region(*id).unwrap().wasteland)
+        .collect();
+    if !wastelands.is_empty() {
+        lines.push(format!("setup_map wastelands {}", join(&wastelands)));
+    }
+    lines
+}
+
+/// The moves `player` sent in `answer`, ignoring anything that doesn't
+/// parse or is sent in someone else's name.
+pub fn parse_moves(answer: &str, player: &str) -> Vec<Move> {
+    let mut moves = Vec::new();
+    for part in answer.split(',').map(|part| part.trim()) {
+        if part.is_empty() || part == "No moves" {
+            continue;
+        }
+        let values = match parse(format!("opponent_moves {}", part)) {
+            Ok(Message::OpponentMoves(values)) => values,
+            _ => {
+                warn!("ignoring move '{}'", part);
+                continue;
+            }
+        };
+        for value in values {
+            match value {
+                OpponentMoveValue::PlaceArmies(ref name, id, armies) if name == player => {
+                    moves.push(Move::PlaceArmies(id, armies));
+                },
+                OpponentMoveValue::AttackTransfer(ref name, source, target, armies) if name == player => {
+                    moves.push(Move::AttackTransfer(source, target, armies));
+                },
+                _ => warn!("ignoring move '{}' not sent as {}", part, player)
+            }
+        }
+    }
+    moves
+}
+
+/// The lines `player` is sent before the picks start.
+fn setup_lines(setup: &GameState, player: usize, offered: &[u64], config: &EngineConfig) -> Vec<String> {
+    let mut lines = vec![
+        format!("settings timebank {}", config.timebank),
+        format!("settings time_per_move {}", config.time_per_move),
+        format!("settings max_rounds {}", setup.settings.max_rounds),
+        format!("settings your_bot {}", player_name(player)),
+        format!("settings opponent_bot {}", player_name(1 - player))
+    ];
+    lines.extend(map_lines(&setup.map));
+    if !offered.is_empty() {
+        lines.push(format!("settings starting_regions {}", join(offered)));
+    }
+    lines.push(format!("settings starting_pick_amount {}", setup.settings.starting_pick_amount));
+    lines
+}
+
+/// The regions `player` can see, their own and every neighbour of those.
+fn visible(map: &GameMap, player: usize) -> Vec<u64> {
+    let mut ids = Vec::new();
+    for region in map.owned_by(player_owner(player)) {
+        ids.push(region.id);
+        ids.extend(region.neighbor_ids.iter().cloned());
+    }
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+fn update_map(map: &GameMap, player: usize) -> String {
+    let mut line = "update_map".to_owned();
+    for id in visible(map, player) {
+        let region = map.region(id).unwrap();
+        line.push_str(&format!(" {} {} {}", id, owner_name(region.owner), region.armies));
+    }
+    line
+}
+
+fn opponent_moves(moves: &[(OwnerValue, Move)]) -> String {
+    let mut line = "opponent_moves".to_owned();
+    for &(owner, ref movement) in moves {
+        line.push_str(&format!(" {}", movement.to_command(&owner_name(owner))));
+    }
+    line
+}
+
+fn map_line(map: &GameMap) -> String {
+    let mut line = "map".to_owned();
+    for id in map.region_ids() {
+        let region = map.region(id).unwrap();
+        line.push_str(&format!(" {} {} {}", id, owner_name(region.owner), region.armies));
+    }
+    line
+}
+
+fn touched(movement: &Move) -> Vec<u64> {
+    match *movement {
+        Move::PlaceArmies(id, _) => vec![id],
+        Move::AttackTransfer(source, target, _) => vec![source, target]
+    }
+}
+
+fn player_name(player: usize) -> String {
+    format!("player{}", player + 1)
+}
+
+fn player_owner(player: usize) -> OwnerValue {
+    if player == 0 {OwnerValue::Ally} else {OwnerValue::Enemy}
+}
+
+fn owner_name(owner: OwnerValue) -> String {
+    match owner {
+        OwnerValue::Ally => player_name(0),
+        OwnerValue::Enemy => player_name(1),
+        OwnerValue::Neutral => "neutral".to_owned()
+    }
+}
+
+fn join(ids: &[u64]) -> String {
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    ids.join(" ")
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+/// Never answers, remembering everything it was sent.
+#[cfg(test)]
+struct Silent {
+    received: Vec<String>
+}
+
+#[cfg(test)]
+impl Player for Silent {
+    fn send(&mut self, line: &str) {
+        self.received.push(line.to_owned());
+    }
+
+    fn request(&mut self, line: &str, _: Duration) -> Option<String> {
+        self.received.push(line.to_owned());
+        None
+    }
+}
+
+/// `game::TEST_SETUP` cut to three rounds, with a wasteland in the middle.
+#[cfg(test)]
+fn test_setup() -> GameState {
+    let mut setup = game::load_setup(game::TEST_SETUP).unwrap();
+    setup.settings.max_rounds = 3;
+    setup.map.upgrade_to_wasteland(3);
+    setup
+}
+
+#[test]
+fn players_only_see_their_surroundings() {
+    let mut map = test_setup().map;
+    map.update_map(1, OwnerValue::Ally, 4);
+    map.update_map(6, OwnerValue::Enemy, 3);
+    assert_eq!(update_map(&map, 0), "update_map 1 player1 4 2 neutral 2");
+    assert_eq!(update_map(&map, 1), "update_map 5 neutral 2 6 player2 3");
+}
+
+#[test]
+fn map_lines_round_trip() {
+    let map = test_setup().map;
+    let lines = map_lines(&map);
+    assert_eq!(lines, vec![
+        "setup_map super_regions 1 2 2 2",
+        "setup_map regions 1 1 2 1 3 1 4 2 5 2 6 2",
+        "setup_map neighbors 1 2 2 3 3 4 4 5 5 6",
+        "setup_map wastelands 3"
+    ]);
+}
+
+#[test]
+fn answers_are_parsed() {
+    let answer = "player1 place_armies 1 5, player2 place_armies 6 5,player1 attack/transfer 1 2 4, nonsense";
+    assert_eq!(parse_moves(answer, "player1"), vec![Move::PlaceArmies(1, 5), Move::AttackTransfer(1, 2, 4)]);
+    assert!(parse_moves("No moves", "player1").is_empty());
+}
+
+#[test]
+fn silent_players_still_get_a_game() {
+    use strategy::seeded_rng;
+    let setup = test_setup();
+    let mut first = Silent { received: Vec::new() };
+    let mut second = Silent { received: Vec::new() };
+    let (result, log) = run_game(&setup, [&mut first, &mut second], &EngineConfig::default(), &mut seeded_rng(0));
+    assert_eq!(result.winner, None);
+    assert_eq!(result.rounds, 3);
+    assert_eq!(log.last().unwrap(), "result none 3");
+    assert_eq!(first.received[0], "settings timebank 10000");
+    // Everything sent is something the bot can read.
+    for line in first.received.iter().chain(second.received.iter()) {
+        assert!(parse(line.clone()).is_ok(), "couldn't parse '{}'", line);
+    }
+    // Neither picked in time so both got the first region on offer.
+    assert_eq!(log.iter().filter(|line| line.ends_with("timed out")).count(), 2 + 2 * 2 * 3);
+    assert!(second.received.contains(&"setup_map opponent_starting_regions 1".to_owned()) ||
+            first.received.contains(&"setup_map opponent_starting_regions 1".to_owned()));
+}
+
+#[test]
+fn commands_split_like_a_shell() {
+    assert_eq!(split_command("target/debug/warbot --strategy planner").unwrap(),
+        vec!["target/debug/warbot", "--strategy", "planner"]);
+    assert_eq!(split_command("\"my bots/warbot\" --weights 'tuned weights.txt' a\\ b ''").unwrap(),
+        vec!["my bots/warbot", "--weights", "tuned weights.txt", "a b", ""]);
+    assert_eq!(split_command("'don\\t' \"say \\\"hi\\\"\"").unwrap(), vec!["don\\t", "say \"hi\""]);
+    assert!(split_command("warbot 'oops").is_err());
+    assert!(split_command("warbot \\").is_err());
+    assert!(split_command("   ").unwrap().is_empty());
+}
@@ -0,0 +1,261 @@
+//! Scoring a position from our side.
+//!
+//! A position is boiled down to a handful of `Features`, each one a
+//! difference between us and the opponent or something that counts against
+//! us, and the features are weighed with `EvalWeights`. Searches want a
+//! value between 0 and 1 so the weighted sum is squashed with a logistic
+//! curve in `evaluate()`, planners comparing positions can use `score()`
+//! directly.
+//!
+//! Weights can be written to and read back from a plain text file with one
+//! `name value` pair per line, which is what `warlib-tune` produces.
+
+use combat::armies_needed;
+use map::{GameMap, OwnerValue};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct EvalWeights {
+    /// Per army of income we get over the opponent.
+    pub income: f64,
+    /// Per army of super region bonus, scaled by how much more of the super
+    /// region we hold than the opponent. Completed super regions count
+    /// towards income instead.
+    pub progress: f64,
+    /// Per army we have on the map over the opponent.
+    pub armies: f64,
+    /// Per region we hold over the opponent.
+    pub regions: f64,
+    /// Subtracted for every one of our regions the opponent could take next
+    /// round with the armies next to it.
+    pub exposure: f64,
+    /// Subtracted per army we expect the opponent to have next round.
+    pub enemy_strength: f64,
+    /// Score at which `evaluate()` gives about 0.73.
+    pub scale: f64
+}
+
+/// The position summarised from our side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Features {
+    pub income: f64,
+    pub progress: f64,
+    pub armies: f64,
+    pub regions: f64,
+    pub exposure: f64,
+    pub enemy_strength: f64
+}
+
+impl Default for EvalWeights {
+    fn default() -> EvalWeights {
+        EvalWeights {
+            income: 1.0,
+            progress: 0.5,
+            armies: 0.05,
+            regions: 0.2,
+            exposure: 0.3,
+            enemy_strength: 0.02,
+            scale: 4.0
+        }
+    }
+}
+
+impl EvalWeights {
+    /// Reads a weights file. Weights it leaves out keep their defaults.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<EvalWeights, String> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        try!(File::open(path).and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| format!("couldn't read {}: {}", path.display(), e)));
+        EvalWeights::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<EvalWeights, String> {
+        let mut weights = EvalWeights::default();
+        for (number, raw_line) in text.lines().enumerate() {
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 2 {
+                return Err(format!("line {}: expected a name and a value", number + 1));
+            }
+            let value = try!(parts[1].parse::<f64>()
+                .map_err(|_| format!("line {}: {} is not a number", number + 1, parts[1])));
+            try!(weights.set(parts[0], value).map_err(|e| format!("line {}: {}", number + 1, e)));
+        }
+        Ok(weights)
+    }
+
+    /// Every weight by name, in the order they are written out.
+    pub fn values(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("income", self.income),
+            ("progress", self.progress),
+            ("armies", self.armies),
+            ("regions", self.regions),
+            ("exposure", self.exposure),
+            ("enemy_strength", self.enemy_strength),
+            ("scale", self.scale)
+        ]
+    }
+
+    pub fn set(&mut self, name: &str, value: f64) -> Result<(), String> {
+        match name {
+            "income" => self.income = value,
+            "progress" => self.progress = value,
+            "armies" => self.armies = value,
+            "regions" => self.regions = value,
+            "exposure" => self.exposure = value,
+            "enemy_strength" => self.enemy_strength = value,
+            "scale" => if value > 0.0 {
+                self.scale = value
+            } else {
+                return Err("scale has to be positive".to_owned());
+            },
+            _ => return Err(format!("unknown weight {}", name))
+        }
+        Ok(())
+    }
+
+    pub fn score(&self, features: &Features) -> f64 {
+        self.income * features.income
+            + self.progress * features.progress
+            + self.armies * features.armies
+            + self.regions * features.regions
+            - self.exposure * features.exposure
+            - self.enemy_strength * features.enemy_strength
+    }
+}
+
+impl fmt::Display for EvalWeights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in self.values() {
+            try!(writeln!(f, "{} {}", name, value));
+        }
+        Ok(())
+    }
+}
+
+pub fn features(map: &GameMap) -> Features {
+    let allies = map.owned_by(OwnerValue::Ally);
+    let enemies = map.owned_by(OwnerValue::Enemy);
+    let armies = |owner: OwnerValue| map.owned_by(owner).iter().map(|region| region.armies).sum::<u64>() as f64;
+    let enemy_armies = armies(OwnerValue::Enemy);
+
+    Features {
+        income: map.income(OwnerValue::Ally) as f64 - map.income(OwnerValue::Enemy) as f64,
+        progress: progress(map, OwnerValue::Ally) - progress(map, OwnerValue::Enemy),
+        armies: armies(OwnerValue::Ally) - enemy_armies,
+        regions: allies.len() as f64 - enemies.len() as f64,
+        exposure: allies.iter().filter(|region| threat(map, region.id) >= armies_needed(region.armies)).count() as f64,
+        // What they hold plus what they get to place before they next move.
+        enemy_strength: enemy_armies + map.income(OwnerValue::Enemy) as f64
+    }
+}
+
+/// The weighted score of `map`, positive when we are ahead.
+pub fn score(map: &GameMap, weights: &EvalWeights) -> f64 {
+    weights.score(&features(map))
+}
+
+/// How good `map` is for us between 0 and 1. Positions where one side has
+/// been wiped out are 0 or 1 whatever the weights say.
+pub fn evaluate(map: &GameMap, weights: &EvalWeights) -> f64 {
+    if map.owned_by(OwnerValue::Ally).is_empty() {
+        return 0.0;
+    } else if map.owned_by(OwnerValue::Enemy).is_empty() {
+        return 1.0;
+    }
+    1.0 / (1.0 + (-score(map, weights) / weights.scale).exp())
+}
+
+/// Bonus armies `owner` is part way towards, every unfinished super region
+/// weighted by the share of it they hold.
+fn progress(map: &GameMap, owner: OwnerValue) -> f64 {
+    let mut progress = 0.0;
+    for id in map.super_region_ids() {
+        let super_region = map.super_region(id).unwrap();
+        if super_region.region_ids.is_empty() || map.owns_super_region(owner, id) {
+            continue;
+        }
+        let held = super_region.region_ids.iter()
+            .filter(|region_id| map.region(**region_id).unwrap().owner == owner)
+            .count();
+        progress += super_region.value as f64 * held as f64 / super_region.region_ids.len() as f64;
+    }
+    progress
+}
+
+/// Armies the opponent could attack `id` with from the regions next to it.
+fn threat(map: &GameMap, id: u64) -> u64 {
+    map.region(id).unwrap().neighbor_ids.iter()
+        .map(|neighbor| map.region(*neighbor).unwrap())
+        .filter(|neighbor| neighbor.owner == OwnerValue::Enemy)
+        .map(|neighbor| neighbor.armies.saturating_sub(1))
+        .sum()
+}
+
+#[cfg(test)]
+fn test_map() -> GameMap {
+    // Super regions 1 - 2 and 3 - 4 worth 2 each, in a line.
+    let mut map = GameMap::new();
+    map.add_super_region(1, 2);
+    map.add_super_region(2, 2);
+    for (id, super_region) in vec![(1, 1), (2, 1), (3, 2), (4, 2)] {
+        map.add_region(id, super_region);
+    }
+    for id in 1..4 {
+        map.add_region_neighbors(id, vec![id + 1]);
+    }
+    map
+}
+
+#[test]
+fn features_are_from_our_side() {
+    let mut map = test_map();
+    map.update_map(1, OwnerValue::Ally, 5);
+    map.update_map(2, OwnerValue::Ally, 2);
+    map.update_map(3, OwnerValue::Enemy, 4);
+    let features = features(&map);
+    assert_eq!(features.income, 2.0);
+    assert_eq!(features.progress, -1.0);
+    assert_eq!(features.armies, 3.0);
+    assert_eq!(features.regions, 1.0);
+    // Three armies can attack out of 3, enough to take the 2 on region 2.
+    assert_eq!(features.exposure, 1.0);
+    assert_eq!(features.enemy_strength, 9.0);
+}
+
+#[test]
+fn better_positions_score_higher() {
+    let weights = EvalWeights::default();
+    let mut map = test_map();
+    map.update_map(1, OwnerValue::Ally, 5);
+    map.update_map(4, OwnerValue::Enemy, 5);
+    let before = evaluate(&map, &weights);
+
+    map.update_map(2, OwnerValue::Ally, 1);
+    assert!(evaluate(&map, &weights) > before);
+
+    map.update_map(1, OwnerValue::Enemy, 1);
+    map.update_map(2, OwnerValue::Enemy, 1);
+    assert_eq!(evaluate(&map, &weights), 0.0);
+}
+
+#[test]
+fn weights_survive_a_round_trip() {
+    let mut weights = EvalWeights::default();
+    weights.exposure = 0.125;
+    weights.scale = 3.5;
+    let parsed = EvalWeights::parse(&format!("# tuned\n{}", weights)).unwrap();
+    assert_eq!(parsed.values(), weights.values());
+    assert!(EvalWeights::parse("income").is_err());
+    assert!(EvalWeights::parse("income x").is_err());
+    assert!(EvalWeights::parse("luck 1").is_err());
+    assert!(EvalWeights::parse("scale 0").is_err());
+}
@@ -0,0 +1,150 @@
+//! Writing a `GameMap` out for other tools to look at.
+//!
+//! `dot()` gives the map as an undirected Graphviz graph, every super region
+//! a cluster outlined in the colour of whoever holds all of it, every region
+//! a node filled with its owner's colour and labelled with its id and
+//! armies. Wastelands are drawn as boxes. Render it with e.g.
+//! `neato -Tsvg round-5.dot > round-5.svg`, or `dot` for a layered layout.
+//!
+//! `json()` gives the same map as data:
+//!
+//! ```text
+//! {"super_regions":[{"id":1,"bonus":4,"regions":[1,2]}],
+//!  "regions":[{"id":1,"super_region":1,"neighbors":[2],"owner":"ally","armies":6,"wasteland":false}, ...],
+//!  "wastelands":[]}
+//! ```
+//!
+//! Both list everything ordered by id, so two exports of the same map are
+//! the same text. The bot writes both every round when it is started with a
+//! directory to dump to, see `Bot::start_recording()`.
+
+use map::{GameMap, OwnerValue};
+
+fn colour(owner: OwnerValue) -> &'static str {
+    match owner {
+        OwnerValue::Ally => "#99ccff",
+        OwnerValue::Enemy => "#ff9999",
+        OwnerValue::Neutral => "#dddddd"
+    }
+}
+
+fn sorted(ids: &[u64]) -> Vec<u64> {
+    let mut ids = ids.to_vec();
+    ids.sort();
+    ids.dedup();
+    ids
+}
+
+/// `map` in Graphviz's DOT language.
+pub fn dot(map: &GameMap) -> String {
+    let mut lines = Vec::new();
+    lines.push("graph map {".to_owned());
+    lines.push("  node [style=filled];".to_owned());
+    for super_id in map.super_region_ids() {
+        let super_region = map.super_region(super_id).unwrap();
+        let holder = if map.owns_super_region(OwnerValue::Ally, super_id) {
+            colour(OwnerValue::Ally)
+        } else if map.owns_super_region(OwnerValue::Enemy, super_id) {
+            colour(OwnerValue::Enemy)
+        } else {
+            "#888888"
+        };
+        lines.push(format!("  subgraph cluster_{} {{", super_id));
+        lines.push(format!("    label=\"super region {} (+{})\";", super_id, super_region.value));
+        lines.push(format!("    color=\"{}\";", holder));
+        lines.push("    penwidth=2;".to_owned());
+        for id in sorted(&super_region.region_ids) {
+            let region = map.region(id).unwrap();
+            lines.push(format!(
+                "    {} [label=\"{}\\n{}\", fillcolor=\"{}\"{}];",
+                id,
+                id,
+                region.armies,
+                colour(region.owner),
+                if region.wasteland {", shape=box"} else {""}
+            ));
+        }
+        lines.push("  }".to_owned());
+    }
+    for id in map.region_ids() {
+        for neighbor in sorted(&map.region(id).unwrap().neighbor_ids) {
+            // Every edge once, from its lower end.
+            if id < neighbor {
+                lines.push(format!("  {} -- {};", id, neighbor));
+            }
+        }
+    }
+    lines.push("}".to_owned());
+    lines.join("\n") + "\n"
+}
+
+/// `map` as JSON, see the module docs.
+pub fn json(map: &GameMap) -> String {
+    let join = |ids: Vec<u64>| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",");
+    let super_regions: Vec<String> = map.super_region_ids().iter().map(|super_id| {
+        let super_region = map.super_region(*super_id).unwrap();
+        format!(
+            "{{\"id\":{},\"bonus\":{},\"regions\":[{}]}}",
+            super_id,
+            super_region.value,
+            join(sorted(&super_region.region_ids))
+        )
+    }).collect();
+    let regions: Vec<String> = map.region_ids().iter().map(|id| {
+        let region = map.region(*id).unwrap();
+        format!(
+            "{{\"id\":{},\"super_region\":{},\"neighbors\":[{}],\"owner\":\"{}\",\"armies\":{},\"wasteland\":{}}}",
+            id,
+            region.super_region_id,
+            join(sorted(&region.neighbor_ids)),
+            region.owner.as_str(),
+            region.armies,
+            region.wasteland
+        )
+    }).collect();
+    let wastelands: Vec<u64> = map.region_ids().into_iter()
+        .filter(|id| map.region(*id).unwrap().wasteland)
+        .collect();
+    format!(
+        "{{\"super_regions\":[{}],\"regions\":[{}],\"wastelands\":[{}]}}\n",
+        super_regions.join(","),
+        regions.join(","),
+        join(wastelands)
+    )
+}
+
+#[cfg(test)]
+fn test_map() -> GameMap {
+    let mut map = GameMap::new();
+    map.add_super_region(1, 2);
+    map.add_super_region(2, 3);
+    map.add_region(1, 1);
+    map.add_region(2, 1);
+    map.add_region(3, 2);
+    map.add_region_neighbors(1, vec![2]);
+    map.add_region_neighbors(2, vec![3]);
+    map.upgrade_to_wasteland(3);
+    map.update_map(1, OwnerValue::Ally, 4);
+    map.update_map(2, OwnerValue::Ally, 1);
+    map
+}
+
+#[test]
+fn maps_export_as_dot() {
+    let dot = dot(&test_map());
+    assert!(dot.starts_with("graph map {\n"));
+    assert!(dot.contains("  subgraph cluster_1 {\n    label=\"super region 1 (+2)\";\n    color=\"#99ccff\";\n"));
+    assert!(dot.contains("    1 [label=\"1\\n4\", fillcolor=\"#99ccff\"];\n"));
+    assert!(dot.contains("    3 [label=\"3\\n6\", fillcolor=\"#dddddd\", shape=box];\n"));
+    assert_eq!(dot.matches(" -- ").count(), 2);
+    assert!(dot.contains("  2 -- 3;\n"));
+}
+
+#[test]
+fn maps_export_as_json() {
+    assert_eq!(json(&test_map()), "{\"super_regions\":[{\"id\":1,\"bonus\":2,\"regions\":[1,2]},{\"id\":2,\"bonus\":3,\"regions\":[3]}],\
+        \"regions\":[{\"id\":1,\"super_region\":1,\"neighbors\":[2],\"owner\":\"ally\",\"armies\":4,\"wasteland\":false},\
+        {\"id\":2,\"super_region\":1,\"neighbors\":[1,3],\"owner\":\"ally\",\"armies\":1,\"wasteland\":false},\
+        {\"id\":3,\"super_region\":2,\"neighbors\":[2],\"owner\":\"neutral\",\"armies\":6,\"wasteland\":true}],\
+        \"wastelands\":[3]}\n");
+}
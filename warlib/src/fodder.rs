@@ -0,0 +1,262 @@
+//! Turning recorded games into fodder files for `tests/runner.rs`.
+//!
+//! Both our replays (see `replay`) and the engine's game logs (see `engine`)
+//! are read into the lines one bot was sent and what it answered. Cut at a
+//! round, that becomes a fodder file ending in the answer that went wrong:
+//!
+//! ```text
+//! # From game.jsonl, seed 42, up to round 3.
+//!
+//! settings your_bot player1
+//! ...
+//! Round 3
+//!
+//! go attack/transfer 10000
+//!
+//! Output from your bot: "player1 attack/transfer 4 5 2"
+//!
+//! # Valid: ![player1 attack/transfer 4 5 2]
+//! ```
+//!
+//! Unless told otherwise the test checks the answer doesn't come back, it is
+//! meant to be edited into what the bot should have done. Round 0 is the
+//! picking phase.
+
+use replay::{Record, read_record};
+
+/// A line sent to the bot, with its answer if it was asked for one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    pub line: String,
+    pub answer: Option<String>
+}
+
+/// One bot's side of a recorded game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    /// The seed the bot played with, if the recording has it.
+    pub seed: Option<u64>,
+    pub exchanges: Vec<Exchange>
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FodderOptions {
+    /// Last round kept, the whole game when `None`.
+    pub round: Option<u64>,
+    /// End at the placements of the last round rather than its moves.
+    pub placements_only: bool,
+    /// Written as the `# Strategy:` header, the runner plays random without.
+    pub strategy: Option<String>,
+    /// The `# Valid:` spec, the last answer must not come back by default.
+    pub valid: Option<String>,
+    /// Where the recording came from, for the file's first comment.
+    pub source: String
+}
+
+/// Whether `text` looks like one of our replays rather than an engine log.
+pub fn is_replay(text: &str) -> bool {
+    text.lines().find(|line| !line.trim().is_empty()).map_or(false, |line| line.trim().starts_with('{'))
+}
+
+/// Reads a replay written by `replay::ReplayWriter`.
+pub fn from_replay(text: &str) -> Result<Recording, String> {
+    let mut recording = Recording {
+        seed: None,
+        exchanges: Vec::new()
+    };
+    for (number, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match try!(read_record(line).map_err(|e| format!("line {}: {}", number + 1, e))) {
+            Record::Start { seed, .. } => recording.seed = Some(seed),
+            Record::Received { line, .. } => recording.exchanges.push(Exchange {
+                line: line,
+                answer: None
+            }),
+            Record::Sent { line, .. } => try!(answer(&mut recording.exchanges, line, number)),
+            Record::Map { .. } => {}
+        }
+    }
+    Ok(recording)
+}
+
+/// Reads `player`'s side of a game log written by `engine::run_game()`.
+pub fn from_engine_log(text: &str, player: &str) -> Result<Recording, String> {
+    let sent = format!("{} < ", player);
+    let received = format!("{} > ", player);
+    let mut exchanges = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        if line.starts_with(&sent) {
+            exchanges.push(Exchange {
+                line: line[sent.len()..].to_owned(),
+                answer: None
+            });
+        } else if line.starts_with(&received) {
+            try!(answer(&mut exchanges, line[received.len()..].to_owned(), number));
+        }
+    }
+    if exchanges.is_empty() {
+        return Err(format!("nothing was sent to {}", player));
+    }
+    Ok(Recording {
+        seed: None,
+        exchanges: exchanges
+    })
+}
+
+fn answer(exchanges: &mut Vec<Exchange>, line: String, number: usize) -> Result<(), String> {
+    match exchanges.last_mut() {
+        Some(exchange) => {
+            exchange.answer = Some(line);
+            Ok(())
+        },
+        None => Err(format!("line {}: an answer before anything was asked", number + 1))
+    }
+}
+
+/// The fodder file for `recording` cut as `options` say.
+pub fn write_fodder(recording: &Recording, options: &FodderOptions) -> Result<String, String> {
+    let requests: Vec<usize> = (0..recording.exchanges.len())
+        .filter(|i| is_request(&recording.exchanges[*i].line))
+        .collect();
+    let last_round = requests.iter().filter(|i| is_placement(&recording.exchanges[**i].line)).count() as u64;
+    let round = options.round.unwrap_or(last_round);
+    if round > last_round {
+        return Err(format!("the game only has {} rounds", last_round));
+    }
+
+    // The last request of the round, or its placements.
+    let mut seen = 0;
+    let mut end = None;
+    for &i in requests.iter() {
+        let line = &recording.exchanges[i].line;
+        if is_placement(line) {
+            seen += 1;
+        }
+        if seen > round || (seen == round && options.placements_only && round > 0 && !is_placement(line)) {
+            break;
+        }
+        end = Some(i);
+    }
+    let end = try!(end.ok_or(format!("nothing was asked by round {}", round)));
+    let last = &recording.exchanges[end];
+    let valid = match (options.valid.as_ref(), last.answer.as_ref()) {
+        (Some(valid), _) => valid.clone(),
+        (None, Some(answer)) => format!("![{}]", answer),
+        (None, None) => return Err(format!("'{}' wasn't answered, there is nothing to check", last.line))
+    };
+
+    let mut lines = Vec::new();
+    let mut header = format!("# From {}", options.source);
+    if let Some(seed) = recording.seed {
+        header.push_str(&format!(", seed {}", seed));
+    }
+    header.push_str(&format!(", up to round {}.", round));
+    lines.push(header);
+    if let Some(ref strategy) = options.strategy {
+        lines.push(format!("# Strategy: {}", strategy));
+    }
+    lines.push(String::new());
+
+    let mut round = 0;
+    for exchange in recording.exchanges[..end + 1].iter() {
+        if is_placement(&exchange.line) {
+            round += 1;
+            lines.push(format!("Round {}", round));
+            lines.push(String::new());
+        }
+        lines.push(exchange.line.clone());
+        lines.push(String::new());
+        if let Some(ref answer) = exchange.answer {
+            lines.push(format!("Output from your bot: \"{}\"", answer));
+            lines.push(String::new());
+        }
+    }
+    lines.push(format!("# Valid: {}", valid));
+    Ok(lines.join("\n") + "\n")
+}
+
+/// Whether the runner waits for an answer to `line`.
+fn is_request(line: &str) -> bool {
+    line.starts_with("go ") || line.starts_with("pick_starting_region")
+}
+
+fn is_placement(line: &str) -> bool {
+    line.starts_with("go place_armies")
+}
+
+#[cfg(test)]
+const TEST_LOG: &'static str = "\
+round 0
+player1 < settings your_bot player1
+player2 < settings your_bot player2
+player1 < pick_starting_region 10000 1 6
+player1 > 1
+player2 < pick_starting_region 10000 6
+player2 > 6
+round 1
+player1 < go place_armies 10000
+player1 > player1 place_armies 1 5
+player2 < go place_armies 10000
+player2 timed out
+player1 < go attack/transfer 10000
+player1 > player1 attack/transfer 1 2 5
+moves player1 place_armies 1 5 player1 attack/transfer 1 2 5
+result none 1
+";
+
+#[test]
+fn engine_logs_are_cut_at_a_round() {
+    let recording = from_engine_log(TEST_LOG, "player1").unwrap();
+    assert_eq!(recording.exchanges.len(), 4);
+    assert_eq!(recording.exchanges[1].answer, Some("1".to_owned()));
+
+    let mut options = FodderOptions {
+        strategy: Some("planner".to_owned()),
+        source: "game.log".to_owned(),
+        ..FodderOptions::default()
+    };
+    let fodder = write_fodder(&recording, &options).unwrap();
+    assert!(fodder.starts_with("# From game.log, up to round 1.\n# Strategy: planner\n"));
+    assert!(fodder.contains("Round 1\n\ngo place_armies 10000\n\nOutput from your bot: \"player1 place_armies 1 5\"\n"));
+    assert!(fodder.ends_with("# Valid: ![player1 attack/transfer 1 2 5]\n"));
+
+    options.placements_only = true;
+    options.valid = Some("[place_armies 1]".to_owned());
+    let fodder = write_fodder(&recording, &options).unwrap();
+    assert!(!fodder.contains("go attack/transfer"));
+    assert!(fodder.ends_with("# Valid: [place_armies 1]\n"));
+
+    options.round = Some(0);
+    assert!(write_fodder(&recording, &options).unwrap().ends_with("# Valid: [place_armies 1]\n"));
+    options.round = Some(2);
+    assert!(write_fodder(&recording, &options).is_err());
+
+    // Player2 never answered the last thing it was asked.
+    let recording = from_engine_log(TEST_LOG, "player2").unwrap();
+    assert!(write_fodder(&recording, &FodderOptions::default()).is_err());
+}
+
+#[test]
+fn replays_become_fodder() {
+    use replay::ReplayWriter;
+    let mut replay = ReplayWriter::new(Vec::new(), false);
+    replay.start(42);
+    replay.received("settings your_bot player1");
+    replay.received("pick_starting_region 10000 1 6");
+    replay.sent("6", 1);
+    let text = String::from_utf8(replay.into_inner()).unwrap();
+    assert!(is_replay(&text));
+    assert!(!is_replay(TEST_LOG));
+
+    let recording = from_replay(&text).unwrap();
+    assert_eq!(recording.seed, Some(42));
+    let options = FodderOptions {
+        source: "game.jsonl".to_owned(),
+        ..FodderOptions::default()
+    };
+    let fodder = write_fodder(&recording, &options).unwrap();
+    assert_eq!(fodder, "# From game.jsonl, seed 42, up to round 0.\n\nsettings your_bot player1\n\n\
+        pick_starting_region 10000 1 6\n\nOutput from your bot: \"6\"\n\n# Valid: ![6]\n");
+}
@@ -0,0 +1,252 @@
+//! Whole games between two strategies, played in memory.
+//!
+//! The map comes from the `setup_map` and `settings` lines of a transcript
+//! like the ones under `tests/fodder`, or from a map file, see `mapfile`.
+//! Picks go in snake order from the starting regions on offer, then every
+//! round both strategies are asked for their moves on their own copy of the
+//! map and the round is played out with `rules::resolve_round()`. There is no
+//! fog, both players see everything.
+//!
+//! Player 0 is `Ally` on the real map and player 1 is `Enemy`, each player is
+//! shown the map with owners swapped so they always play as `Ally`.
+
+use map::{GameMap, OwnerValue};
+use mapfile::{MapFile, is_map_file};
+use moves::repair_moves;
+use ordering::order_moves;
+use parser::{Message, SetupMapValue, parse};
+use picks::first_player_picks;
+use rand::{Rng, XorShiftRng};
+use rules::resolve_round;
+use state::GameState;
+use strategy::Strategy;
+
+/// Rounds played when the transcript doesn't set `max_rounds`.
+const DEFAULT_MAX_ROUNDS: u64 = 100;
+/// Picks each player gets when the transcript doesn't say.
+const DEFAULT_PICK_AMOUNT: u64 = 4;
+/// Time the strategies are told they have left, in milliseconds.
+const TIMEBANK: u64 = 10000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameResult {
+    /// `None` for a draw.
+    pub winner: Option<usize>,
+    pub rounds: u64,
+    /// Regions each player held at the end.
+    pub regions: [usize; 2]
+}
+
+/// Builds the starting position from a transcript or a map file. Only the
+/// map and the settings about the game itself are kept, who the players were
+/// and what they did is ignored.
+pub fn load_setup(transcript: &str) -> Result<GameState, String> {
+    let mut state = GameState::new();
+    if is_map_file(transcript) {
+        let file = try!(MapFile::parse(transcript));
+        state.map = file.map;
+        state.settings.starting_regions = file.starting_regions;
+    }
+    for raw_line in transcript.lines() {
+        let line = raw_line.trim();
+        if !line.starts_with("setup_map") && !line.starts_with("settings") {
+            continue;
+        }
+        match parse(line.to_owned()) {
+            Ok(Message::SetupMap(SetupMapValue::OpponentStartingRegions(_))) => {},
+            Ok(Message::SetupMap(message)) => state.setup_map(message),
+            Ok(Message::Settings(setting)) => state.apply_setting(setting),
+            Ok(_) => {},
+            Err(e) => return Err(format!("couldn't parse '{}': {}", line, e))
+        }
+    }
+    if state.map.region_ids().is_empty() {
+        return Err("no regions in the transcript".to_owned());
+    }
+    if state.settings.max_rounds == 0 {
+        state.settings.max_rounds = DEFAULT_MAX_ROUNDS;
+    }
+    if state.settings.starting_pick_amount == 0 {
+        state.settings.starting_pick_amount = DEFAULT_PICK_AMOUNT;
+    }
+    state.settings.timebank = TIMEBANK;
+    Ok(state)
+}
+
+/// Plays a game from `setup` to the end. When no starting regions are set a
+/// random region of every super region is offered.
+pub fn play(setup: &GameState, players: [&mut Strategy; 2], rng: &mut XorShiftRng) -> GameResult {
+    let mut players = players;
+    let mut states = [player_state(setup, 0), player_state(setup, 1)];
+    let mut map = setup.map.clone();
+
+    let offered = offered_regions(setup, rng);
+    for state in states.iter_mut() {
+        state.settings.starting_regions = offered.clone();
+    }
+    pick_regions(&mut map, &mut players, &mut states, offered, rng);
+
+    let mut round = 0;
+    while round < setup.settings.max_rounds && !is_over(&map) {
+        round += 1;
+        let mut moves = [Vec::new(), Vec::new()];
+        for player in 0..2 {
+            let state = &mut states[player];
+            state.map = view(&map, player);
+            state.settings.starting_armies = state.map.income(OwnerValue::Ally);
+            state.start_round();
+            let placements = players[player].place_armies(state, TIMEBANK, rng);
+            for movement in repair_moves(state, placements) {
+                state.map.record_move(movement);
+            }
+            let attacks = players[player].attack_transfer(state, TIMEBANK, rng);
+            let attacks = order_moves(&state.map, repair_moves(state, attacks), &players[player].ordering());
+            for movement in attacks {
+                state.map.record_move(movement);
+            }
+            moves[player] = state.map.issued().to_vec();
+        }
+        resolve_round(&mut map, &moves[0], &moves[1], rng);
+    }
+
+    outcome(&map, round)
+}
+
+/// The starting regions on offer in `setup`, a random region of every super
+/// region when it doesn't set any.
+pub fn offered_regions(setup: &GameState, rng: &mut XorShiftRng) -> Vec<u64> {
+    if setup.settings.starting_regions.is_empty() {
+        setup.map.super_region_ids().into_iter()
+            .filter_map(|id| rng.choose(&setup.map.super_region(id).unwrap().region_ids).cloned())
+            .collect()
+    } else {
+        setup.settings.starting_regions.clone()
+    }
+}
+
+/// Whether either player has been wiped out.
+pub fn is_over(map: &GameMap) -> bool {
+    map.owned_by(OwnerValue::Ally).is_empty() || map.owned_by(OwnerValue::Enemy).is_empty()
+}
+
+/// The result of a game that ended on `map` after `rounds`, whoever holds
+/// the most regions wins.
+pub fn outcome(map: &GameMap, rounds: u64) -> GameResult {
+    let regions = [map.owned_by(OwnerValue::Ally).len(), map.owned_by(OwnerValue::Enemy).len()];
+    let winner = if regions[0] > regions[1] {
+        Some(0)
+    } else if regions[1] > regions[0] {
+        Some(1)
+    } else {
+        None
+    };
+    GameResult {
+        winner: winner,
+        rounds: rounds,
+        regions: regions
+    }
+}
+
+/// `map` as `player` sees it, with their regions as `Ally`.
+pub fn view(map: &GameMap, player: usize) -> GameMap {
+    let mut view = map.clone();
+    if player == 1 {
+        for id in map.region_ids() {
+            let region = map.region(id).unwrap();
+            let owner = match region.owner {
+                OwnerValue::Ally => OwnerValue::Enemy,
+                OwnerValue::Enemy => OwnerValue::Ally,
+                OwnerValue::Neutral => OwnerValue::Neutral
+            };
+            view.update_map(id, owner, region.armies);
+        }
+    }
+    view
+}
+
+fn player_state(setup: &GameState, player: usize) -> GameState {
+    let mut state = GameState::new();
+    state.settings = setup.settings.clone();
+    state.settings.name = format!("player{}", player + 1);
+    state.settings.opponent = format!("player{}", 2 - player);
+    state.map = setup.map.clone();
+    state
+}
+
+/// Hands out the starting regions in snake order, a random player picking
+/// first. Picks that aren't on offer are replaced by the first region that
+/// is, like the engine does for a bot that times out.
+fn pick_regions(map: &mut GameMap, players: &mut [&mut Strategy; 2], states: &mut [GameState; 2], mut offered: Vec<u64>, rng: &mut XorShiftRng) {
+    let starting = offered.clone();
+    let amount = states[0].settings.starting_pick_amount as usize;
+    let first = if rng.gen() {0} else {1};
+    let mut index = 0;
+    while index < 2 * amount && !offered.is_empty() {
+        let player = if first_player_picks(index) {first} else {1 - first};
+        let state = &mut states[player];
+        state.picks.observe(&starting, &offered);
+        let choice = match players[player].pick_starting_region(state, &offered, rng) {
+            Some(id) if offered.contains(&id) => id,
+            _ => offered[0]
+        };
+        state.picks.record(choice);
+        offered.retain(|id| *id != choice);
+        let owner = if player == 0 {OwnerValue::Ally} else {OwnerValue::Enemy};
+        let armies = map.region(choice).unwrap().armies;
+        map.update_map(choice, owner, armies);
+        index += 1;
+    }
+}
+
+/// A line of six regions over two super regions, starting at either end,
+/// for the tests of the modules that play games.
+#[cfg(test)]
+pub const TEST_SETUP: &'static str = "
+settings max_rounds 30
+setup_map super_regions 1 2 2 2
+setup_map regions 1 1 2 1 3 1 4 2 5 2 6 2
+setup_map neighbors 1 2 2 3 3 4 4 5 5 6
+settings starting_regions 1 6
+settings starting_pick_amount 1
+";
+
+#[test]
+fn setup_comes_from_the_transcript() {
+    let setup = load_setup(TEST_SETUP).unwrap();
+    assert_eq!(setup.map.region_ids(), vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(setup.settings.max_rounds, 30);
+    assert_eq!(setup.settings.starting_regions, vec![1, 6]);
+    assert!(load_setup("settings max_rounds 30").is_err());
+}
+
+#[test]
+fn views_swap_the_owners() {
+    let mut map = load_setup(TEST_SETUP).unwrap().map;
+    map.update_map(1, OwnerValue::Ally, 3);
+    map.update_map(6, OwnerValue::Enemy, 4);
+    let view = view(&map, 1);
+    assert_eq!(view.region(1).unwrap().owner, OwnerValue::Enemy);
+    assert_eq!(view.region(6).unwrap().owner, OwnerValue::Ally);
+    assert_eq!(view.region(6).unwrap().armies, 4);
+}
+
+#[test]
+fn games_are_repeatable() {
+    use strategy::{RandomStrategy, seeded_rng};
+    let setup = load_setup(TEST_SETUP).unwrap();
+    let results: Vec<GameResult> = (0..2).map(|_| {
+        play(&setup, [&mut RandomStrategy::new(), &mut RandomStrategy::new()], &mut seeded_rng(3))
+    }).collect();
+    assert_eq!(results[0], results[1]);
+}
+
+#[test]
+fn games_are_played_to_the_end() {
+    use strategy::{RandomStrategy, seeded_rng};
+    let setup = load_setup(TEST_SETUP).unwrap();
+    let mut first = RandomStrategy::new();
+    let mut second = RandomStrategy::new();
+    let result = play(&setup, [&mut first, &mut second], &mut seeded_rng(7));
+    assert!(result.rounds > 0 && result.rounds <= 30);
+    assert!(result.rounds == 30 || result.regions[0] == 0 || result.regions[1] == 0);
+}
@@ -2,68 +2,145 @@
 extern crate log;
 extern crate rand;
 
+pub mod arena;
+pub mod combat;
+pub mod conquest;
+pub mod defence;
+pub mod engine;
+pub mod eval;
+pub mod export;
+pub mod fodder;
+pub mod game;
+pub mod logistics;
 pub mod map;
+pub mod mapfile;
+pub mod mapgen;
+pub mod movegen;
+pub mod moves;
+pub mod ordering;
 pub mod parser;
+pub mod picks;
+pub mod ratings;
+pub mod replay;
+pub mod rules;
+pub mod state;
+pub mod strategy;
+pub mod viewer;
 
-use parser::{Message, SettingsValue, SetupMapValue, OpponentMoveValue, parse};
-use rand::{thread_rng, sample};
-use std::sync::mpsc::{Receiver, Sender, channel};
+use moves::{Move, repair_moves};
+use ordering::order_moves;
+use parser::{Message, OpponentMoveValue, parse};
+use rand::XorShiftRng;
+use replay::ReplayWriter;
+use state::GameState;
+use strategy::{RandomStrategy, Strategy, StrategyOptions, seeded_rng};
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, SendError, Sender, TryRecvError, channel};
 use std::thread;
+use std::time::Instant;
+
+/// Environment variable holding the seed for the bot's random choices.
+pub const SEED_VAR: &'static str = "WARBOT_SEED";
 
 pub struct Bot {
-    settings: Settings,
-    map: map::GameMap,
+    state: GameState,
+    strategy: Box<Strategy + Send>,
+    rng: XorShiftRng,
     output: Sender<String>,
-    output_buffer: String
+    output_buffer: String,
+    replay: Option<ReplayWriter<Box<Write + Send>>>,
+    /// Where to write the map every round, see `dump_map()`.
+    dump_dir: Option<PathBuf>
 }
 
-// Using 64 bits because I assume the compiler/platform we are targetting is
-// also 64 bit.
-#[derive(Debug)]
-struct Settings {
-    timebank: u64,
-    time_per_move: u64,
-    max_rounds: u64,
-    name: String,
-    opponent: String,
-    starting_regions: Vec<u64>,
-    starting_pick_amount: u64,
-    starting_armies: u64
+/// Handle to a running bot thread.
+///
+/// Lines are fed in with `send()`, dropping the input side with `stop()` tells
+/// the bot the game is over, and `join()` waits for the thread to finish and
+/// reports whether it exited cleanly or crashed.
+pub struct BotHandle {
+    input: Option<Sender<String>>,
+    thread: Option<thread::JoinHandle<()>>
 }
 
 impl Bot {
-    pub fn spawn() -> (Sender<String>, Receiver<String>) {
+    /// Starts the bot on its own thread, returning a handle to control it and
+    /// the receiving end of its responses. The responses channel hangs up once
+    /// the bot thread has exited, whether it stopped or crashed.
+    pub fn start() -> (BotHandle, Receiver<String>) {
+        Bot::start_with(Box::new(RandomStrategy::new()), default_seed())
+    }
+
+    /// Like `start()` but playing with the given strategy, every random
+    /// choice it makes comes from `seed`.
+    pub fn start_with(strategy: Box<Strategy + Send>, seed: u64) -> (BotHandle, Receiver<String>) {
+        Bot::start_recording(strategy, seed, None, None)
+    }
+
+    /// Like `start_with()`, writing everything the bot reads and sends to
+    /// `replay` if there is one and the map every round to `dump_dir` if
+    /// there is one.
+    pub fn start_recording(strategy: Box<Strategy + Send>, seed: u64, replay: Option<ReplayWriter<Box<Write + Send>>>, dump_dir: Option<PathBuf>) -> (BotHandle, Receiver<String>) {
         let (input_tx, input_rx) = channel();
         let (output_tx, output_rx) = channel();
-        thread::spawn(move || {
-            let mut bot = Bot::new(output_tx);
+        let thread = thread::spawn(move || {
+            info!("seed {}", seed);
+            let mut bot = Bot::new(output_tx, strategy, seed);
+            bot.replay = replay;
+            bot.dump_dir = dump_dir;
+            if let Some(ref mut replay) = bot.replay {
+                replay.start(seed);
+            }
             loop {
                 match input_rx.try_recv() {
                     Ok(line) => bot.read_line(line),
-                    Err(_) => bot.calculate()
+                    Err(TryRecvError::Empty) => {
+                        // Think while the engine is busy, then sleep until
+                        // it has something for us rather than spinning.
+                        bot.calculate();
+                        match input_rx.recv() {
+                            Ok(line) => bot.read_line(line),
+                            Err(_) => break
+                        }
+                    },
+                    Err(TryRecvError::Disconnected) => break
                 }
             }
+            info!("input closed, bot shutting down");
         });
-        (input_tx, output_rx)
+        let handle = BotHandle {
+            input: Some(input_tx),
+            thread: Some(thread)
+        };
+        (handle, output_rx)
     }
 
-    fn new(output: Sender<String>) -> Bot {
-        let settings = Settings{
-            timebank: 0,
-            time_per_move: 0,
-            max_rounds: 0,
-            name: "default".to_owned(),
-            opponent: "default".to_owned(),
-            starting_regions: Vec::new(),
-            starting_pick_amount: 0,
-            starting_armies: 0
-        };
+    /// Starts a detached bot and returns just its channels. The bot thread
+    /// exits once the returned `Sender` is dropped.
+    pub fn spawn() -> (Sender<String>, Receiver<String>) {
+        Bot::spawn_seeded(default_seed())
+    }
+
+    /// Like `spawn()` with a fixed seed, so the same input always gets the
+    /// same responses.
+    pub fn spawn_seeded(seed: u64) -> (Sender<String>, Receiver<String>) {
+        let (mut handle, output_rx) = Bot::start_with(Box::new(RandomStrategy::new()), seed);
+        let input_tx = handle.input.take().unwrap();
+        (input_tx, output_rx)
+    }
 
+    fn new(output: Sender<String>, strategy: Box<Strategy + Send>, seed: u64) -> Bot {
         Bot {
-            settings: settings,
-            map: map::GameMap::new(),
+            state: GameState::new(),
+            strategy: strategy,
+            rng: seeded_rng(seed),
             output: output,
             output_buffer: String::new(),
+            replay: None,
+            dump_dir: None
         }
     }
 
@@ -73,68 +150,72 @@ impl Bot {
 
     fn read_line(&mut self, line: String) {
         info!("read_line: '{}'", line);
+        let started = Instant::now();
+        if let Some(ref mut replay) = self.replay {
+            replay.received(&line);
+        }
         match parse(line) {
             Ok(message) => match message {
-                Message::SetupMap(map_message) => self.process_map_message(map_message),
+                Message::SetupMap(map_message) => self.state.setup_map(map_message),
                 Message::Settings(setting) => {
-                    self.process_settings(setting);
+                    self.state.apply_setting(setting);
                 },
                 Message::UpdateMap(regions) => {
+                    let expected = self.state.map.expected_armies();
                     let mut found = Vec::new();
 
                     for (id, raw_owner, armies) in regions {
                         found.push(id);
                         let owner = self.name_to_owner_value(raw_owner).unwrap();
-                        self.map.update_map(id, owner, armies);
+                        self.state.map.update_map(id, owner, armies);
                     }
 
-                    self.map.update_fog(found);
+                    self.state.map.update_fog(found);
+                    self.state.map.reconcile(expected);
                 },
                 Message::OpponentMoves(moves) => for movement in moves {
                     match movement {
                         OpponentMoveValue::PlaceArmies(_, id, _) => {
-                            self.map.mark_as_enemy(id);
+                            self.state.map.mark_as_enemy(id);
                         },
                         OpponentMoveValue::AttackTransfer(_, source_id, _, _) => {
-                            self.map.mark_as_enemy(source_id);
+                            self.state.map.mark_as_enemy(source_id);
                         },
                     }
                 },
                 Message::PickStartingRegion(_, regions) => {
-                    let mut best = 0.0;
-                    let mut choice = 0;
-                    for id in regions.iter() {
-                        let current = self.map.starting_pick_value(id);
-                        if current > best {
-                            best = current;
-                            choice = *id;
+                    self.state.picks.observe(&self.state.settings.starting_regions, &regions);
+                    let choice = match self.strategy.pick_starting_region(&self.state, &regions, &mut self.rng) {
+                        Some(choice) => Some(choice),
+                        None => {
+                            // Not answering would leave the engine waiting
+                            // until we time out.
+                            warn!("the strategy didn't pick, taking the first region offered");
+                            regions.first().cloned()
                         }
-                    }
-
-                    let response = format!("{}", choice);
-                    self.output_buffer = self.queue(response);
-                },
-                Message::GoPlaceArmies(_) => {
-                    let regions = self.map.allies();
-                    let mut rng = thread_rng();
-                    let choices = sample(&mut rng, regions.iter(), self.settings.starting_armies as usize);
-                    for region in choices {
-                        let response = format!("{} place_armies {} {}", self.settings.name, region.id, 1);
+                    };
+                    if let Some(choice) = choice {
+                        self.state.picks.record(choice);
+                        let response = format!("{}", choice);
                         self.output_buffer = self.queue(response);
                     }
                 },
-                Message::GoAttackTransfer(_) => {
-                    let regions = self.map.allies();
-                    let mut rng = thread_rng();
-                    for region in regions {
-                        if region.armies >= 4 {
-                            let choices = sample(&mut rng, region.neighbor_ids.iter(), 1);
-                            if let Some(target) = choices.get(0) {
-                                let response = format!("{} attack/transfer {} {} {}", self.settings.name, region.id, target, 3);
-                                self.output_buffer = self.queue(response);
-                            }
-                        }
+                Message::GoPlaceArmies(time) => {
+                    self.state.start_round();
+                    info!("round {} ({:?})", self.state.round, self.state.phase());
+                    if let Some(ref mut replay) = self.replay {
+                        replay.snapshot(self.state.round, &self.state.map);
                     }
+                    self.dump_map();
+                    let moves = self.strategy.place_armies(&self.state, time, &mut self.rng);
+                    self.issue(moves);
+                    if self.output_buffer.is_empty() {
+                        self.output_buffer = "No moves".to_owned();
+                    }
+                },
+                Message::GoAttackTransfer(time) => {
+                    let moves = self.strategy.attack_transfer(&self.state, time, &mut self.rng);
+                    self.issue(moves);
                     if self.output_buffer.is_empty() {
                         self.output_buffer = "No moves".to_owned();
                     }
@@ -144,73 +225,65 @@ impl Bot {
                 error!("Parser returned: {}", e)
             }
         }
-        self.send();
+        self.send(started);
+    }
+
+    /// Repairs `moves` against what was already sent this turn, puts them in
+    /// the order the strategy wants, queues whatever survives and records it
+    /// on the map.
+    fn issue(&mut self, moves: Vec<Move>) {
+        let moves = repair_moves(&self.state, moves);
+        let moves = order_moves(&self.state.map, moves, &self.strategy.ordering());
+        for movement in moves {
+            let response = movement.to_command(&self.state.settings.name);
+            self.output_buffer = self.queue(response);
+            self.state.map.record_move(movement);
+        }
+    }
+
+    /// Writes the map to `dump_dir` if there is one, as
+    /// `<name>-round-<n>.dot` and `.json` so two bots can share the
+    /// directory. A failure is only logged.
+    fn dump_map(&self) {
+        if let Some(ref dir) = self.dump_dir {
+            let name = format!("{}-round-{}", self.state.settings.name, self.state.round);
+            let files = [("dot", export::dot(&self.state.map)), ("json", export::json(&self.state.map))];
+            for &(extension, ref contents) in files.iter() {
+                let path = dir.join(&name).with_extension(extension);
+                if let Err(e) = File::create(&path).and_then(|mut file| file.write_all(contents.as_bytes())) {
+                    warn!("couldn't write {}: {}", path.display(), e);
+                }
+            }
+        }
     }
 
     fn queue(&self, message: String) -> String {
         if self.output_buffer.is_empty() {
             message
         } else {
-            format!("{} {}", self.output_buffer, message)
+            format!("{}, {}", self.output_buffer, message)
         }
     }
 
-    fn send(&mut self) {
+    /// Sends whatever is queued, `started` is when the line it answers came
+    /// in.
+    fn send(&mut self, started: Instant) {
         if self.output_buffer.len() > 0 {
             info!("send: '{}'", self.output_buffer);
             let response = self.output_buffer.clone();
             self.output_buffer = String::new();
-            self.output.send(response).unwrap();
-        }
-    }
-
-    fn process_map_message(&mut self, message: SetupMapValue) {
-        match message {
-            SetupMapValue::SuperRegions(super_regions) => {
-                for (id, value) in super_regions {
-                    self.map.add_super_region(id, value);
-                }
-            },
-            SetupMapValue::Regions(regions) => {
-                for (id, super_region) in regions {
-                    self.map.add_region(id, super_region);
-                }
-            },
-            SetupMapValue::Neighbors(new_neighbors) => {
-                for (id, neighbors) in new_neighbors {
-                    self.map.add_region_neighbors(id, neighbors);
-                }
-            },
-            SetupMapValue::Wastelands(wastelands) => {
-                for id in wastelands {
-                    self.map.upgrade_to_wasteland(id);
-                }
-            },
-            SetupMapValue::OpponentStartingRegions(enemies) => {
-                for id in enemies {
-                    self.map.mark_as_enemy(id);
-                }
+            if let Some(ref mut replay) = self.replay {
+                let took = started.elapsed();
+                replay.sent(&response, took.as_secs() * 1000 + (took.subsec_nanos() / 1_000_000) as u64);
             }
+            self.output.send(response).unwrap();
         }
     }
 
-    fn process_settings(&mut self, message: SettingsValue) {
-        match message {
-            SettingsValue::Timebank(time) => self.settings.timebank = time,
-            SettingsValue::TimePerMove(time) => self.settings.time_per_move = time,
-            SettingsValue::MaxRounds(rounds) => self.settings.max_rounds = rounds,
-            SettingsValue::YourBot(name) => self.settings.name = name,
-            SettingsValue::OpponentBot(name) => self.settings.opponent = name,
-            SettingsValue::StartingRegions(region_ids) => self.settings.starting_regions = region_ids,
-            SettingsValue::StartingPickAmount(value) => self.settings.starting_pick_amount = value,
-            SettingsValue::StartingArmies(value) => self.settings.starting_armies = value
-        };
-    }
-
     fn name_to_owner_value(&self, name: String) -> Result<map::OwnerValue, &'static str> {
-        if name == self.settings.name {
+        if name == self.state.settings.name {
             Ok(map::OwnerValue::Ally)
-        } else if name == self.settings.opponent {
+        } else if name == self.state.settings.opponent {
             Ok(map::OwnerValue::Enemy)
         } else if name == "neutral" {
             Ok(map::OwnerValue::Neutral)
@@ -220,3 +293,108 @@ impl Bot {
         }
     }
 }
+
+/// Plays a game over stdin and stdout like theaigames.com expects, until
+/// stdin is closed, recording it to `replay` and dumping the map to
+/// `dump_dir` if they are there. Returns the exit code for the process, 1 if
+/// the bot crashed.
+pub fn play_stdio(strategy: Box<Strategy + Send>, seed: u64, replay: Option<ReplayWriter<Box<Write + Send>>>, dump_dir: Option<PathBuf>) -> i32 {
+    let (bot, responses) = Bot::start_recording(strategy, seed, replay, dump_dir);
+
+    // Responses have to go out as soon as the bot produces them, the engine
+    // waits on each one before sending the next round.
+    let printer = thread::spawn(move || {
+        let stdout = io::stdout();
+        for response in responses.iter() {
+            let mut handle = stdout.lock();
+            if let Err(e) = writeln!(handle, "{}", response).and_then(|_| handle.flush()) {
+                error!("failed to write response: {}", e);
+                break;
+            }
+        }
+    });
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        match line {
+            Ok(line) => if let Err(_) = bot.send(line) {
+                error!("bot stopped accepting input");
+                break;
+            },
+            Err(e) => {
+                error!("failed to read input: {}", e);
+                break;
+            }
+        }
+    }
+
+    let exit_code = match bot.join() {
+        Ok(()) => {
+            info!("game over, bot exited cleanly");
+            0
+        },
+        Err(cause) => {
+            error!("bot crashed: {}", cause);
+            1
+        }
+    };
+    printer.join().unwrap_or(());
+    exit_code
+}
+
+/// Plays the strategy `strategy::by_name()` knows as `name`, with the default
+/// options, over stdin and stdout like the reference bots do. Returns the
+/// exit code for the process.
+pub fn play_reference(name: &str, seed: u64) -> i32 {
+    let strategy = strategy::by_name(name, &StrategyOptions::default()).expect("no such reference strategy");
+    info!("playing with the {} strategy", name);
+    play_stdio(strategy, seed, None, None)
+}
+
+/// The seed in `WARBOT_SEED` if it is set, otherwise a random one.
+pub fn default_seed() -> u64 {
+    match env::var(SEED_VAR) {
+        Ok(value) => match value.parse() {
+            Ok(seed) => seed,
+            Err(_) => {
+                warn!("ignoring {}={}, it isn't a number", SEED_VAR, value);
+                rand::random()
+            }
+        },
+        Err(_) => rand::random()
+    }
+}
+
+impl BotHandle {
+    /// Passes a single line of engine input to the bot. Fails if the bot has
+    /// been stopped or its thread has died.
+    pub fn send(&self, line: String) -> Result<(), SendError<String>> {
+        match self.input {
+            Some(ref input) => input.send(line),
+            None => Err(SendError(line))
+        }
+    }
+
+    /// Closes the bot's input, letting it finish the line it is on and exit.
+    pub fn stop(&mut self) {
+        self.input = None;
+    }
+
+    /// Stops the bot if needed and waits for its thread to exit. Returns
+    /// `Err` with the panic message if the bot crashed.
+    pub fn join(mut self) -> Result<(), String> {
+        self.stop();
+        match self.thread.take().unwrap().join() {
+            Ok(()) => Ok(()),
+            Err(cause) => {
+                if let Some(message) = cause.downcast_ref::<&'static str>() {
+                    Err((*message).to_owned())
+                } else if let Some(message) = cause.downcast_ref::<String>() {
+                    Err(message.clone())
+                } else {
+                    Err("bot thread panicked".to_owned())
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Getting idle armies to where they are needed.
+//!
+//! Armies in interior regions, ones with nothing but our regions around
+//! them, can't attack anything and nothing attacks them. Every round each
+//! interior region with armies to spare sends them one step along the
+//! shortest path through our own regions towards the frontier region that
+//! wants them most for how far away it is.
+//!
+//! Only interior regions send armies. A frontier region never hands its
+//! armies back to the interior, they would just have to walk out again.
+
+use map::{GameMap, OwnerValue};
+use moves::Move;
+use std::collections::HashMap;
+
+/// How much each of our frontier regions wants reinforcements. Every
+/// frontier region is worth 1, `threatened` and `staging` add to that.
+pub fn frontier_values(map: &GameMap, threatened: &[(u64, f64)], staging: &[u64]) -> HashMap<u64, f64> {
+    let mut values: HashMap<u64, f64> = map.owned_by(OwnerValue::Ally).iter()
+        .filter(|region| map.is_border(OwnerValue::Ally, region.id))
+        .map(|region| (region.id, 1.0))
+        .collect();
+    for &(id, value) in threatened {
+        if let Some(entry) = values.get_mut(&id) {
+            *entry += value;
+        }
+    }
+    for id in staging {
+        if let Some(entry) = values.get_mut(id) {
+            *entry += 1.0;
+        }
+    }
+    values
+}
+
+/// Transfers moving the spare armies of interior regions towards the
+/// frontier. `reserved` holds armies regions have to keep at home.
+pub fn route(map: &GameMap, frontier: &HashMap<u64, f64>, reserved: &HashMap<u64, u64>) -> Vec<Move> {
+    let mut moves = Vec::new();
+    for region in map.owned_by(OwnerValue::Ally) {
+        if map.is_border(OwnerValue::Ally, region.id) {
+            continue;
+        }
+        let keep = reserved.get(&region.id).cloned().unwrap_or(1).max(1);
+        let armies = (region.armies + map.placed_armies(region.id)).saturating_sub(keep + map.sent_armies(region.id));
+        if armies == 0 {
+            continue;
+        }
+        let destination = match destination(map, frontier, region.id) {
+            Some(id) => id,
+            None => continue
+        };
+        if let Some(step) = next_step(map, region.id, destination) {
+            moves.push(Move::AttackTransfer(region.id, step, armies));
+        }
+    }
+    moves
+}
+
+/// The frontier region reachable through our regions with the best value
+/// per step from `id`.
+fn destination(map: &GameMap, frontier: &HashMap<u64, f64>, id: u64) -> Option<u64> {
+    let distances = map.owned_distances(OwnerValue::Ally, &[id]);
+    let mut best = None;
+    let mut best_value = 0.0;
+    let mut ids: Vec<&u64> = frontier.keys().collect();
+    ids.sort();
+    for frontier_id in ids {
+        if let Some(distance) = distances.get(frontier_id) {
+            let value = frontier[frontier_id] / (*distance).max(1) as f64;
+            if value > best_value {
+                best = Some(*frontier_id);
+                best_value = value;
+            }
+        }
+    }
+    best
+}
+
+/// Our neighbour of `id` on the shortest path through our regions to
+/// `destination`.
+fn next_step(map: &GameMap, id: u64, destination: u64) -> Option<u64> {
+    let distances = map.owned_distances(OwnerValue::Ally, &[destination]);
+    map.region(id).unwrap().neighbor_ids.iter()
+        .filter_map(|neighbor| distances.get(neighbor).map(|distance| (*distance, *neighbor)))
+        .min()
+        .map(|(_, neighbor)| neighbor)
+}
+
+#[cfg(test)]
+fn test_map() -> GameMap {
+    // 1 - 2 - 3 - 4 - 5 with a branch 2 - 6, we hold 1 to 4 and 6, 5 is the
+    // enemy's.
+    let mut map = GameMap::new();
+    map.add_super_region(1, 2);
+    for id in 1..7 {
+        map.add_region(id, 1);
+    }
+    for id in 1..5 {
+        map.add_region_neighbors(id, vec![id + 1]);
+    }
+    map.add_region_neighbors(2, vec![6]);
+    for id in vec![1, 2, 3, 4, 6] {
+        map.update_map(id, OwnerValue::Ally, 4);
+    }
+    map.update_map(5, OwnerValue::Enemy, 4);
+    map
+}
+
+#[test]
+fn interior_armies_walk_to_the_frontier() {
+    let map = test_map();
+    let frontier = frontier_values(&map, &[], &[]);
+    assert_eq!(frontier.keys().cloned().collect::<Vec<u64>>(), vec![4]);
+    let moves = route(&map, &frontier, &HashMap::new());
+    assert_eq!(moves, vec![
+        Move::AttackTransfer(1, 2, 3),
+        Move::AttackTransfer(2, 3, 3),
+        Move::AttackTransfer(3, 4, 3),
+        Move::AttackTransfer(6, 2, 3)
+    ]);
+}
+
+#[test]
+fn frontier_never_feeds_the_interior() {
+    // Like do_not_move_from_unlocked_to_locked: 14 borders the neutrals and
+    // 13 behind it is locked in, nothing may go from 14 to 13.
+    let mut map = GameMap::new();
+    map.add_super_region(1, 2);
+    for id in vec![13, 14, 15] {
+        map.add_region(id, 1);
+    }
+    map.add_region_neighbors(13, vec![14]);
+    map.add_region_neighbors(14, vec![15]);
+    map.update_map(13, OwnerValue::Ally, 1);
+    map.update_map(14, OwnerValue::Ally, 5);
+    let frontier = frontier_values(&map, &[], &[]);
+    assert!(route(&map, &frontier, &HashMap::new()).is_empty());
+    map.update_map(13, OwnerValue::Ally, 3);
+    assert_eq!(route(&map, &frontier, &HashMap::new()), vec![Move::AttackTransfer(13, 14, 2)]);
+}
+
+#[test]
+fn threatened_regions_pull_harder() {
+    let mut map = test_map();
+    // 6 now borders a neutral region as well.
+    map.add_region(7, 1);
+    map.add_region_neighbors(6, vec![7]);
+    let frontier = frontier_values(&map, &[(6, 3.0)], &[]);
+    let moves = route(&map, &frontier, &HashMap::new());
+    assert!(moves.contains(&Move::AttackTransfer(2, 6, 3)));
+    // Two steps to a region worth 4 beats one step to a region worth 1.
+    assert!(moves.contains(&Move::AttackTransfer(3, 2, 3)));
+}
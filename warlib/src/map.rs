@@ -1,39 +1,60 @@
-use std::collections::HashMap;
+use moves::Move;
+use std::collections::{HashMap, VecDeque};
 
-#[derive(Debug)]
+/// Armies every player gets each round before super region bonuses.
+pub const BASE_INCOME: u64 = 5;
+
+#[derive(Debug, Clone)]
 pub struct GameMap {
     super_regions: HashMap<u64, SuperRegion>,
     regions: HashMap<u64, Region>,
+    // Moves we have issued since the last update_map, the engine doesn't
+    // apply them until the end of the round so the regions above still hold
+    // the armies from before them.
+    issued: Vec<Move>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SuperRegion {
     pub id: u64,
     pub value: u64,
     pub region_ids: Vec<u64>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Region {
     pub id: u64,
     pub super_region_id: u64,
     pub neighbor_ids: Vec<u64>,
     pub armies: u64,
-    pub owner: OwnerValue
+    pub owner: OwnerValue,
+    pub wasteland: bool
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OwnerValue{
     Ally,
     Enemy,
     Neutral
 }
 
+impl OwnerValue {
+    /// The owner as replays and map exports write it.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            OwnerValue::Ally => "ally",
+            OwnerValue::Enemy => "enemy",
+            OwnerValue::Neutral => "neutral"
+        }
+    }
+}
+
 impl GameMap {
     pub fn new() -> GameMap {
         GameMap {
             super_regions: HashMap::with_capacity(30),
             regions: HashMap::with_capacity(120),
+            issued: Vec::new()
         }
     }
 
@@ -63,7 +84,8 @@ impl GameMap {
     pub fn upgrade_to_wasteland(&mut self, id: u64) {
         let region = self.regions.get_mut(&id).unwrap();
         region.armies = 6;
-        region.owner = OwnerValue::Neutral
+        region.owner = OwnerValue::Neutral;
+        region.wasteland = true;
     }
 
     pub fn mark_as_enemy(&mut self, id: u64) {
@@ -85,23 +107,182 @@ impl GameMap {
         }
     }
 
+    /// Remembers a move we sent this round.
+    pub fn record_move(&mut self, movement: Move) {
+        self.issued.push(movement);
+    }
+
+    pub fn issued(&self) -> &[Move] {
+        &self.issued
+    }
+
+    /// Armies we placed on `id` this round.
+    pub fn placed_armies(&self, id: u64) -> u64 {
+        self.issued.iter().map(|movement| match *movement {
+            Move::PlaceArmies(region_id, armies) if region_id == id => armies,
+            _ => 0
+        }).sum()
+    }
+
+    /// Armies already sent out of `id` this round.
+    pub fn sent_armies(&self, id: u64) -> u64 {
+        self.issued.iter().map(|movement| match *movement {
+            Move::AttackTransfer(source, _, armies) if source == id => armies,
+            _ => 0
+        }).sum()
+    }
+
+    /// Armies transferred into `id` from our own regions this round, they
+    /// only arrive once the moves are processed so can't move on again.
+    pub fn arriving_armies(&self, id: u64) -> u64 {
+        self.issued.iter().map(|movement| match *movement {
+            Move::AttackTransfer(source, target, armies) if target == id && self.is_ally(source) => armies,
+            _ => 0
+        }).sum()
+    }
+
+    /// Armies that can still leave `id` this round, after what we placed there
+    /// and minus what has already been sent and the one that must stay.
+    pub fn available_armies(&self, id: u64) -> u64 {
+        match self.regions.get(&id) {
+            Some(region) if region.owner == OwnerValue::Ally => {
+                (region.armies + self.placed_armies(id)).saturating_sub(1 + self.sent_armies(id))
+            },
+            _ => 0
+        }
+    }
+
+    /// What each region we touched should hold once our moves land, assuming
+    /// nobody attacks it. Attack targets are left out, the fight decides them.
+    pub fn expected_armies(&self) -> HashMap<u64, u64> {
+        let mut expected = HashMap::new();
+        for movement in self.issued.iter() {
+            let touched = match *movement {
+                Move::PlaceArmies(id, _) => vec![id],
+                Move::AttackTransfer(source, target, _) => if self.is_ally(target) {
+                    vec![source, target]
+                } else {
+                    vec![source]
+                }
+            };
+            for id in touched {
+                let armies = (self.regions.get(&id).unwrap().armies + self.placed_armies(id)
+                    + self.arriving_armies(id)).saturating_sub(self.sent_armies(id));
+                expected.insert(id, armies);
+            }
+        }
+        expected
+    }
+
+    /// Compares the `expected_armies()` taken before an update_map with what
+    /// the engine reported, then forgets the moves from the finished round.
+    pub fn reconcile(&mut self, expected: HashMap<u64, u64>) {
+        for (id, armies) in expected {
+            let region = self.regions.get(&id).unwrap();
+            if region.owner != OwnerValue::Ally {
+                info!("lost region {} we had issued moves for", id);
+            } else if region.armies != armies {
+                debug!("region {} has {} armies, expected {}", id, region.armies, armies);
+            }
+        }
+        self.issued.clear();
+    }
+
+    pub fn region(&self, id: u64) -> Option<&Region> {
+        self.regions.get(&id)
+    }
+
+    pub fn super_region(&self, id: u64) -> Option<&SuperRegion> {
+        self.super_regions.get(&id)
+    }
+
+    /// Number of steps from the closest of `from` to every region that can be
+    /// reached from them.
+    pub fn distances(&self, from: &[u64]) -> HashMap<u64, u64> {
+        self.distances_through(from, None)
+    }
+
+    /// Like `distances()` but only stepping through regions held by `owner`.
+    pub fn owned_distances(&self, owner: OwnerValue, from: &[u64]) -> HashMap<u64, u64> {
+        self.distances_through(from, Some(owner))
+    }
+
+    fn distances_through(&self, from: &[u64], owner: Option<OwnerValue>) -> HashMap<u64, u64> {
+        let mut distances = HashMap::with_capacity(self.regions.len());
+        let mut queue = VecDeque::new();
+        for id in from {
+            distances.insert(*id, 0);
+            queue.push_back(*id);
+        }
+        while let Some(id) = queue.pop_front() {
+            let distance = *distances.get(&id).unwrap();
+            for neighbor in self.regions.get(&id).unwrap().neighbor_ids.iter() {
+                let passable = owner.map_or(true, |owner| self.regions.get(neighbor).unwrap().owner == owner);
+                if passable && !distances.contains_key(neighbor) {
+                    distances.insert(*neighbor, distance + 1);
+                    queue.push_back(*neighbor);
+                }
+            }
+        }
+        distances
+    }
+
+    /// Every region id, in order so anything walking the map behaves the same
+    /// from one run to the next.
+    pub fn region_ids(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self.regions.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn super_region_ids(&self) -> Vec<u64> {
+        let mut ids: Vec<u64> = self.super_regions.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Regions held by `owner`, ordered by id.
+    pub fn owned_by(&self, owner: OwnerValue) -> Vec<&Region> {
+        let mut owned: Vec<&Region> = self.regions.values()
+            .filter(|region| region.owner == owner)
+            .collect();
+        owned.sort_by_key(|region| region.id);
+        owned
+    }
+
     pub fn allies(&self) -> Vec<&Region> {
-        self.regions.iter()
-            .filter_map(|(_, region)| if region.owner == OwnerValue::Ally {Some(region)} else {None})
-            .collect()
+        self.owned_by(OwnerValue::Ally)
     }
 
-    pub fn starting_pick_value(&self, region_id: &u64) -> f64 {
-        let super_region = self.super_regions.get(&self.regions.get(region_id).unwrap().super_region_id).unwrap();
+    /// Whether `owner` holds every region of the super region.
+    pub fn owns_super_region(&self, owner: OwnerValue, super_region_id: u64) -> bool {
+        match self.super_regions.get(&super_region_id) {
+            Some(super_region) => super_region.region_ids.iter()
+                .all(|id| self.regions.get(id).unwrap().owner == owner),
+            None => false
+        }
+    }
 
-        let mut armies = 0;
-        for (id, region) in self.regions.iter() {
-            if super_region.region_ids.contains(&id) {
-                armies += region.armies;
+    /// Armies `owner` gets to place each round with what they hold now.
+    pub fn income(&self, owner: OwnerValue) -> u64 {
+        let mut income = if owner == OwnerValue::Neutral {0} else {BASE_INCOME};
+        for (id, super_region) in self.super_regions.iter() {
+            if owner != OwnerValue::Neutral && self.owns_super_region(owner, *id) {
+                income += super_region.value;
             }
         }
+        income
+    }
 
-        super_region.value as f64 / (armies) as f64
+    /// Whether `id` touches a region not held by `owner`.
+    pub fn is_border(&self, owner: OwnerValue, id: u64) -> bool {
+        self.regions.get(&id).map_or(false, |region| {
+            region.neighbor_ids.iter().any(|neighbor| self.regions.get(neighbor).unwrap().owner != owner)
+        })
+    }
+
+    fn is_ally(&self, id: u64) -> bool {
+        self.regions.get(&id).map_or(false, |region| region.owner == OwnerValue::Ally)
     }
 }
 
@@ -122,7 +303,58 @@ impl Region {
             super_region_id: super_region_id,
             neighbor_ids: Vec::with_capacity(10),
             armies: 2,
-            owner: OwnerValue::Neutral
+            owner: OwnerValue::Neutral,
+            wasteland: false
         }
     }
 }
+
+#[cfg(test)]
+fn line_map() -> GameMap {
+    // 1 - 2 - 3 all ours with 4 armies each.
+    let mut map = GameMap::new();
+    map.add_super_region(1, 2);
+    for id in 1..4 {
+        map.add_region(id, 1);
+        map.update_map(id, OwnerValue::Ally, 4);
+    }
+    map.add_region_neighbors(1, vec![2]);
+    map.add_region_neighbors(2, vec![3]);
+    map
+}
+
+#[test]
+fn moved_troops_are_not_available() {
+    let mut map = line_map();
+    map.record_move(Move::PlaceArmies(1, 2));
+    map.record_move(Move::AttackTransfer(1, 2, 5));
+    assert_eq!(map.available_armies(1), 0);
+    assert_eq!(map.arriving_armies(2), 5);
+    assert_eq!(map.available_armies(2), 3);
+    map.record_move(Move::AttackTransfer(2, 3, 2));
+    assert_eq!(map.available_armies(2), 1);
+}
+
+#[test]
+fn reconcile_forgets_the_round() {
+    let mut map = line_map();
+    map.record_move(Move::PlaceArmies(2, 3));
+    map.record_move(Move::AttackTransfer(2, 3, 6));
+    let expected = map.expected_armies();
+    assert_eq!(expected.get(&2), Some(&1));
+    assert_eq!(expected.get(&3), Some(&10));
+    map.update_map(2, OwnerValue::Ally, 1);
+    map.update_map(3, OwnerValue::Ally, 10);
+    map.reconcile(expected);
+    assert!(map.issued().is_empty());
+    assert_eq!(map.available_armies(3), 9);
+}
+
+#[test]
+fn owned_distances_stay_inside() {
+    let mut map = line_map();
+    assert_eq!(map.owned_distances(OwnerValue::Ally, &[1]).get(&3), Some(&2));
+    map.update_map(2, OwnerValue::Neutral, 2);
+    assert_eq!(map.owned_distances(OwnerValue::Ally, &[1]).get(&3), None);
+    assert_eq!(map.distances(&[1]).get(&3), Some(&2));
+}
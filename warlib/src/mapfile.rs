@@ -0,0 +1,303 @@
+//! A map on its own, outside of any transcript.
+//!
+//! Map files are plain text, one entry per line, `#` starting a comment:
+//!
+//! ```text
+//! # A line of three regions over two super regions.
+//! super_region 1 2          id and bonus
+//! super_region 2 3
+//! region 1 1                id and super region
+//! region 2 1
+//! region 3 2
+//! neighbors 1 2             a region and its neighbours
+//! neighbors 2 3
+//! wastelands 3              regions starting with 6 neutral armies
+//! starting_regions 1 3      regions offered to pick from
+//! ```
+//!
+//! Borders go both ways, listing one from either end is enough. Without
+//! `starting_regions` a random region of every super region is offered, see
+//! `game::offered_regions()`.
+//!
+//! `game::load_setup()` takes map files as well as transcripts, so the
+//! engine, tournaments and tuning all play on either.
+
+use engine::map_lines;
+use map::GameMap;
+use state::GameState;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct MapFile {
+    pub map: GameMap,
+    pub starting_regions: Vec<u64>
+}
+
+/// Whether the first entry of `text` is one a map file starts with rather
+/// than a transcript line.
+pub fn is_map_file(text: &str) -> bool {
+    text.lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with("#"))
+        .and_then(|line| line.split_whitespace().next())
+        .map_or(false, |word| KEYWORDS.contains(&word))
+}
+
+const KEYWORDS: [&'static str; 5] = ["super_region", "region", "neighbors", "wastelands", "starting_regions"];
+
+impl MapFile {
+    /// The map and starting regions of a game loaded from a transcript.
+    pub fn from_setup(setup: &GameState) -> MapFile {
+        MapFile {
+            map: setup.map.clone(),
+            starting_regions: setup.settings.starting_regions.clone()
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<MapFile, String> {
+        let path = path.as_ref();
+        let mut contents = String::new();
+        try!(File::open(path).and_then(|mut file| file.read_to_string(&mut contents))
+            .map_err(|e| format!("couldn't read {}: {}", path.display(), e)));
+        MapFile::parse(&contents).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Reads a map file, refusing maps that don't hold together: unknown or
+    /// repeated ids, empty super regions, regions that can't be reached from
+    /// the rest, wastelands offered as starting regions.
+    pub fn parse(text: &str) -> Result<MapFile, String> {
+        let mut super_regions: Vec<(usize, u64, u64)> = Vec::new();
+        let mut regions: Vec<(usize, u64, u64)> = Vec::new();
+        let mut borders: Vec<(usize, u64, u64)> = Vec::new();
+        let mut wastelands: Vec<(usize, u64)> = Vec::new();
+        let mut starting_regions: Vec<(usize, u64)> = Vec::new();
+        for (index, raw_line) in text.lines().enumerate() {
+            let number = index + 1;
+            let line = raw_line.trim();
+            if line.is_empty() || line.starts_with("#") {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap();
+            if !KEYWORDS.contains(&keyword) {
+                return Err(format!("line {}: unknown entry {}", number, keyword));
+            }
+            let mut values = Vec::new();
+            for word in words {
+                values.push(try!(word.parse::<u64>().map_err(|_| format!("line {}: {} is not an id or a number", number, word))));
+            }
+            match keyword {
+                "super_region" | "region" => {
+                    if values.len() != 2 {
+                        return Err(format!("line {}: {} takes an id and a {}", number, keyword,
+                            if keyword == "region" {"super region"} else {"bonus"}));
+                    }
+                    let entries = if keyword == "region" {&mut regions} else {&mut super_regions};
+                    entries.push((number, values[0], values[1]));
+                },
+                "neighbors" => {
+                    if values.len() < 2 {
+                        return Err(format!("line {}: neighbors takes a region and at least one neighbour", number));
+                    }
+                    for neighbor in values[1..].iter() {
+                        borders.push((number, values[0], *neighbor));
+                    }
+                },
+                "wastelands" | "starting_regions" => {
+                    if values.is_empty() {
+                        return Err(format!("line {}: {} takes at least one region", number, keyword));
+                    }
+                    let entries = if keyword == "wastelands" {&mut wastelands} else {&mut starting_regions};
+                    entries.extend(values.into_iter().map(|id| (number, id)));
+                },
+                _ => unreachable!()
+            }
+        }
+
+        let mut map = GameMap::new();
+        for &(number, id, bonus) in super_regions.iter() {
+            if map.super_region(id).is_some() {
+                return Err(format!("line {}: super region {} is there twice", number, id));
+            }
+            map.add_super_region(id, bonus);
+        }
+        for &(number, id, super_region) in regions.iter() {
+            if map.region(id).is_some() {
+                return Err(format!("line {}: region {} is there twice", number, id));
+            }
+            if map.super_region(super_region).is_none() {
+                return Err(format!("line {}: region {} is in super region {}, which isn't there", number, id, super_region));
+            }
+            map.add_region(id, super_region);
+        }
+        let known = |number: usize, id: u64, map: &GameMap| if map.region(id).is_none() {
+            Err(format!("line {}: there is no region {}", number, id))
+        } else {
+            Ok(())
+        };
+        let mut seen = HashSet::new();
+        for &(number, id, neighbor) in borders.iter() {
+            try!(known(number, id, &map));
+            try!(known(number, neighbor, &map));
+            if id == neighbor {
+                return Err(format!("line {}: region {} can't border itself", number, id));
+            }
+            if seen.insert((id.min(neighbor), id.max(neighbor))) {
+                map.add_region_neighbors(id, vec![neighbor]);
+            }
+        }
+        for &(number, id) in wastelands.iter() {
+            try!(known(number, id, &map));
+            map.upgrade_to_wasteland(id);
+        }
+        let mut offered = Vec::new();
+        for &(number, id) in starting_regions.iter() {
+            try!(known(number, id, &map));
+            if map.region(id).unwrap().wasteland {
+                return Err(format!("line {}: region {} is a wasteland, it can't be a starting region", number, id));
+            }
+            if offered.contains(&id) {
+                return Err(format!("line {}: starting region {} is there twice", number, id));
+            }
+            offered.push(id);
+        }
+
+        let ids = map.region_ids();
+        if ids.is_empty() {
+            return Err("there are no regions".to_owned());
+        }
+        for super_id in map.super_region_ids() {
+            if map.super_region(super_id).unwrap().region_ids.is_empty() {
+                return Err(format!("super region {} has no regions", super_id));
+            }
+        }
+        let reached = map.distances(&ids[..1]);
+        if let Some(id) = ids.iter().find(|id| !reached.contains_key(id)) {
+            return Err(format!("region {} can't be reached from region {}", id, ids[0]));
+        }
+
+        Ok(MapFile {
+            map: map,
+            starting_regions: offered
+        })
+    }
+
+    /// The map file text, everything ordered by id and every border listed
+    /// once from its lower end.
+    pub fn to_text(&self) -> String {
+        let mut lines = Vec::new();
+        for id in self.map.super_region_ids() {
+            lines.push(format!("super_region {} {}", id, self.map.super_region(id).unwrap().value));
+        }
+        let ids = self.map.region_ids();
+        for id in ids.iter() {
+            lines.push(format!("region {} {}", id, self.map.region(*id).unwrap().super_region_id));
+        }
+        for id in ids.iter() {
+            let mut higher: Vec<u64> = self.map.region(*id).unwrap().neighbor_ids.iter()
+                .cloned()
+                .filter(|neighbor| neighbor > id)
+                .collect();
+            higher.sort();
+            higher.dedup();
+            if !higher.is_empty() {
+                lines.push(format!("neighbors {} {}", id, join(&higher)));
+            }
+        }
+        let wastelands: Vec<u64> = ids.iter().cloned().filter(|id| self.map.region(*id).unwrap().wasteland).collect();
+        if !wastelands.is_empty() {
+            lines.push(format!("wastelands {}", join(&wastelands)));
+        }
+        if !self.starting_regions.is_empty() {
+            lines.push(format!("starting_regions {}", join(&self.starting_regions)));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    /// The map as the engine sends it, `setup_map` lines followed by the
+    /// starting regions if there are any.
+    pub fn setup_lines(&self) -> Vec<String> {
+        let mut lines = map_lines(&self.map);
+        if !self.starting_regions.is_empty() {
+            lines.push(format!("settings starting_regions {}", join(&self.starting_regions)));
+        }
+        lines
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        File::create(path)
+            .and_then(|mut file| file.write_all(self.to_text().as_bytes()))
+            .map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+    }
+}
+
+fn join(ids: &[u64]) -> String {
+    ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+const TEST_MAP: &'static str = "
+# A line of three regions over two super regions.
+super_region 1 2
+super_region 2 3
+region 1 1
+region 2 1
+region 3 2
+neighbors 2 1
+neighbors 2 3
+neighbors 3 2
+wastelands 3
+starting_regions 1
+";
+
+#[test]
+fn map_files_are_read_and_written() {
+    let file = MapFile::parse(TEST_MAP).unwrap();
+    assert_eq!(file.map.region(2).unwrap().neighbor_ids.len(), 2);
+    assert!(file.map.region(3).unwrap().wasteland);
+    assert_eq!(file.starting_regions, vec![1]);
+    let text = file.to_text();
+    assert_eq!(text, "super_region 1 2\nsuper_region 2 3\nregion 1 1\nregion 2 1\nregion 3 2\n\
+        neighbors 1 2\nneighbors 2 3\nwastelands 3\nstarting_regions 1\n");
+    assert_eq!(MapFile::parse(&text).unwrap().to_text(), text);
+}
+
+#[test]
+fn broken_maps_are_refused() {
+    let broken = |text: &str| MapFile::parse(text).unwrap_err();
+    assert_eq!(broken("super_region 1\n"), "line 1: super_region takes an id and a bonus");
+    assert_eq!(broken("super_region 1 x\n"), "line 1: x is not an id or a number");
+    assert_eq!(broken("territory 1 1\n"), "line 1: unknown entry territory");
+    assert_eq!(broken("settings timebank 10000\n"), "line 1: unknown entry settings");
+    assert_eq!(broken("super_region 1 1\nregion 1 2\n"), "line 2: region 1 is in super region 2, which isn't there");
+    assert_eq!(broken("super_region 1 1\nregion 1 1\nregion 1 1\n"), "line 3: region 1 is there twice");
+    assert_eq!(broken("super_region 1 1\nregion 1 1\nneighbors 1 2\n"), "line 3: there is no region 2");
+    assert_eq!(broken("super_region 1 1\nregion 1 1\nneighbors 1 1\n"), "line 3: region 1 can't border itself");
+    assert_eq!(broken("super_region 1 1\nsuper_region 2 1\nregion 1 1\n"), "super region 2 has no regions");
+    assert_eq!(broken("super_region 1 1\nregion 1 1\nregion 2 1\n"), "region 2 can't be reached from region 1");
+    assert_eq!(broken("super_region 1 1\nregion 1 1\nwastelands 1\nstarting_regions 1\n"),
+        "line 4: region 1 is a wasteland, it can't be a starting region");
+    assert_eq!(broken("# nothing\n"), "there are no regions");
+}
+
+#[test]
+fn transcripts_convert_to_map_files() {
+    use game::load_setup;
+    let transcript = include_str!("../tests/fodder/better_economy.txt");
+    assert!(!is_map_file(transcript));
+    let setup = load_setup(transcript).unwrap();
+    let text = MapFile::from_setup(&setup).to_text();
+    assert!(is_map_file(&text));
+
+    let reloaded = load_setup(&text).unwrap();
+    assert_eq!(map_lines(&reloaded.map), map_lines(&setup.map));
+    assert_eq!(reloaded.settings.starting_regions, setup.settings.starting_regions);
+    assert_eq!(reloaded.settings.max_rounds, 100);
+
+    let lines = MapFile::from_setup(&setup).setup_lines().join("\n");
+    assert_eq!(load_setup(&lines).unwrap().settings.starting_regions, setup.settings.starting_regions);
+}
@@ -0,0 +1,291 @@
+//! Random maps, for playing on shapes no real map has.
+//!
+//! Regions are scattered over a square and joined to their nearest
+//! neighbours, shortest borders first, skipping any that would cross one
+//! already drawn, so the map can be drawn flat like a real one. Whatever is
+//! left in pieces is then joined by the shortest borders between them,
+//! crossing or not. Super regions grow from a random region each, taking
+//! turns claiming a neighbouring region, which keeps every super region in
+//! one piece.
+//!
+//! Bonuses follow super region size like on the real maps, `bonus_per_region`
+//! for every region give or take up to `bonus_jitter`. Every super region
+//! offers one starting region, never a wasteland.
+//!
+//! The same config and seed always give the same map.
+
+use map::GameMap;
+use mapfile::MapFile;
+use rand::{Rng, XorShiftRng};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WastelandPlacement {
+    /// Any region that isn't offered as a starting region.
+    Anywhere,
+    /// Only regions bordering another super region, where they block the
+    /// way like on the real maps.
+    Borders
+}
+
+#[derive(Debug, Clone)]
+pub struct MapGenConfig {
+    pub regions: u64,
+    pub super_regions: u64,
+    /// Regions each region is joined to at most, before joining the pieces.
+    pub neighbors: usize,
+    pub bonus_per_region: f64,
+    pub bonus_jitter: u64,
+    pub wastelands: u64,
+    pub wasteland_placement: WastelandPlacement
+}
+
+impl Default for MapGenConfig {
+    /// About the size of the theaigames.com maps.
+    fn default() -> MapGenConfig {
+        MapGenConfig {
+            regions: 42,
+            super_regions: 8,
+            neighbors: 4,
+            bonus_per_region: 0.7,
+            bonus_jitter: 1,
+            wastelands: 4,
+            wasteland_placement: WastelandPlacement::Borders
+        }
+    }
+}
+
+/// A map built as `config` says, `Err` if it asks for the impossible.
+pub fn generate(config: &MapGenConfig, rng: &mut XorShiftRng) -> Result<MapFile, String> {
+    let count = config.regions as usize;
+    if config.super_regions == 0 || config.super_regions > config.regions {
+        return Err(format!("can't split {} regions into {} super regions", config.regions, config.super_regions));
+    }
+    if config.neighbors == 0 {
+        return Err("regions need at least one neighbour".to_owned());
+    }
+
+    let points: Vec<(f64, f64)> = (0..count).map(|_| (rng.gen::<f64>(), rng.gen::<f64>())).collect();
+    let borders = join_points(&points, config.neighbors);
+    let mut adjacent = vec![Vec::new(); count];
+    for &(a, b) in borders.iter() {
+        adjacent[a].push(b);
+        adjacent[b].push(a);
+    }
+    let owners = grow_super_regions(&adjacent, config.super_regions as usize, rng);
+
+    // Ids count from 1 like on the real maps.
+    let mut map = GameMap::new();
+    let mut members = vec![Vec::new(); config.super_regions as usize];
+    for (region, owner) in owners.iter().enumerate() {
+        members[*owner].push(region);
+    }
+    for (index, regions) in members.iter().enumerate() {
+        let size = regions.len() as f64;
+        let jitter = if config.bonus_jitter == 0 {
+            0
+        } else {
+            rng.gen_range(0, 2 * config.bonus_jitter + 1) as i64 - config.bonus_jitter as i64
+        };
+        let bonus = ((size * config.bonus_per_region).round() as i64 + jitter).max(1) as u64;
+        map.add_super_region(index as u64 + 1, bonus);
+    }
+    for (region, owner) in owners.iter().enumerate() {
+        map.add_region(region as u64 + 1, *owner as u64 + 1);
+    }
+    for &(a, b) in borders.iter() {
+        map.add_region_neighbors(a as u64 + 1, vec![b as u64 + 1]);
+    }
+
+    let starting_regions: Vec<u64> = members.iter()
+        .map(|regions| *rng.choose(regions).unwrap() as u64 + 1)
+        .collect();
+    let mut candidates: Vec<u64> = (1..count as u64 + 1)
+        .filter(|id| !starting_regions.contains(id))
+        .filter(|id| match config.wasteland_placement {
+            WastelandPlacement::Anywhere => true,
+            WastelandPlacement::Borders => {
+                let owner = owners[*id as usize - 1];
+                adjacent[*id as usize - 1].iter().any(|neighbor| owners[*neighbor] != owner)
+            }
+        })
+        .collect();
+    if (candidates.len() as u64) < config.wastelands {
+        return Err(format!("only {} regions can be wastelands, {} asked for", candidates.len(), config.wastelands));
+    }
+    rng.shuffle(&mut candidates);
+    for id in candidates.into_iter().take(config.wastelands as usize) {
+        map.upgrade_to_wasteland(id);
+    }
+
+    Ok(MapFile {
+        map: map,
+        starting_regions: starting_regions
+    })
+}
+
+/// Borders between the points as pairs of indices, lower index first.
+fn join_points(points: &[(f64, f64)], neighbors: usize) -> Vec<(usize, usize)> {
+    let distance = |a: usize, b: usize| {
+        let (dx, dy) = (points[a].0 - points[b].0, points[a].1 - points[b].1);
+        (dx * dx + dy * dy).sqrt()
+    };
+    let mut candidates = HashSet::new();
+    for a in 0..points.len() {
+        let mut nearest: Vec<usize> = (0..points.len()).filter(|b| *b != a).collect();
+        nearest.sort_by(|x, y| distance(a, *x).partial_cmp(&distance(a, *y)).unwrap());
+        for b in nearest.into_iter().take(neighbors) {
+            candidates.insert((a.min(b), a.max(b)));
+        }
+    }
+    let mut candidates: Vec<(usize, usize)> = candidates.into_iter().collect();
+    candidates.sort_by(|x, y| distance(x.0, x.1).partial_cmp(&distance(y.0, y.1)).unwrap().then(x.cmp(y)));
+
+    let mut borders: Vec<(usize, usize)> = Vec::new();
+    for &(a, b) in candidates.iter() {
+        if !borders.iter().any(|&(c, d)| crosses(points, (a, b), (c, d))) {
+            borders.push((a, b));
+        }
+    }
+
+    // Join whatever is still in pieces, the closest pair of regions between
+    // the piece holding region 0 and the rest each time.
+    loop {
+        let reached = reachable(&borders);
+        if reached.len() == points.len() {
+            break;
+        }
+        let mut closest = None;
+        for a in (0..points.len()).filter(|a| reached.contains(a)) {
+            for b in (0..points.len()).filter(|b| !reached.contains(b)) {
+                let length = distance(a, b);
+                if closest.map_or(true, |(_, _, best)| length < best) {
+                    closest = Some((a, b, length));
+                }
+            }
+        }
+        let (a, b, _) = closest.unwrap();
+        borders.push((a.min(b), a.max(b)));
+    }
+    borders.sort();
+    borders
+}
+
+/// Points reachable from point 0.
+fn reachable(borders: &[(usize, usize)]) -> HashSet<usize> {
+    let mut reached = HashSet::new();
+    let mut queue = vec![0];
+    reached.insert(0);
+    while let Some(point) = queue.pop() {
+        for &(a, b) in borders.iter() {
+            let other = if a == point {b} else if b == point {a} else {continue};
+            if reached.insert(other) {
+                queue.push(other);
+            }
+        }
+    }
+    reached
+}
+
+/// Whether two borders cross, sharing an end doesn't count.
+fn crosses(points: &[(f64, f64)], first: (usize, usize), second: (usize, usize)) -> bool {
+    if first.0 == second.0 || first.0 == second.1 || first.1 == second.0 || first.1 == second.1 {
+        return false;
+    }
+    let side = |a: usize, b: usize, c: usize| {
+        let (ax, ay) = points[a];
+        let (bx, by) = points[b];
+        let (cx, cy) = points[c];
+        (bx - ax) * (cy - ay) - (by - ay) * (cx - ax)
+    };
+    side(first.0, first.1, second.0) * side(first.0, first.1, second.1) < 0.0
+        && side(second.0, second.1, first.0) * side(second.0, second.1, first.1) < 0.0
+}
+
+/// The super region of every region, `count` of them grown from random
+/// regions over `adjacent` a region at a time, in turns.
+fn grow_super_regions(adjacent: &[Vec<usize>], count: usize, rng: &mut XorShiftRng) -> Vec<usize> {
+    let mut owners: Vec<Option<usize>> = vec![None; adjacent.len()];
+    let mut order: Vec<usize> = (0..adjacent.len()).collect();
+    rng.shuffle(&mut order);
+    for (owner, region) in order.into_iter().take(count).enumerate() {
+        owners[region] = Some(owner);
+    }
+    let mut left = adjacent.len() - count;
+    while left > 0 {
+        for owner in 0..count {
+            let mut frontier: Vec<usize> = (0..adjacent.len())
+                .filter(|region| owners[*region].is_none())
+                .filter(|region| adjacent[*region].iter().any(|neighbor| owners[*neighbor] == Some(owner)))
+                .collect();
+            if frontier.is_empty() {
+                continue;
+            }
+            frontier.sort();
+            let region = *rng.choose(&frontier).unwrap();
+            owners[region] = Some(owner);
+            left -= 1;
+            if left == 0 {
+                break;
+            }
+        }
+    }
+    owners.into_iter().map(|owner| owner.unwrap()).collect()
+}
+
+#[test]
+fn generated_maps_hold_together() {
+    use strategy::seeded_rng;
+    for seed in 0..10 {
+        let file = generate(&MapGenConfig::default(), &mut seeded_rng(seed)).unwrap();
+        // Parsing checks ids, borders, wastelands and that it is connected.
+        let parsed = MapFile::parse(&file.to_text()).unwrap();
+        assert_eq!(parsed.map.region_ids().len(), 42);
+        assert_eq!(parsed.map.super_region_ids().len(), 8);
+        assert_eq!(parsed.starting_regions.len(), 8);
+        let wastelands = parsed.map.region_ids().into_iter().filter(|id| parsed.map.region(*id).unwrap().wasteland).count();
+        assert_eq!(wastelands, 4);
+        for super_id in parsed.map.super_region_ids() {
+            let regions = parsed.map.super_region(super_id).unwrap().region_ids.clone();
+            // Every super region is in one piece.
+            let mut reached = vec![regions[0]];
+            let mut i = 0;
+            while i < reached.len() {
+                for neighbor in parsed.map.region(reached[i]).unwrap().neighbor_ids.clone() {
+                    if regions.contains(&neighbor) && !reached.contains(&neighbor) {
+                        reached.push(neighbor);
+                    }
+                }
+                i += 1;
+            }
+            assert_eq!(reached.len(), regions.len());
+        }
+    }
+    let config = MapGenConfig::default();
+    assert_eq!(generate(&config, &mut seeded_rng(3)).unwrap().to_text(), generate(&config, &mut seeded_rng(3)).unwrap().to_text());
+    let too_many = MapGenConfig {
+        super_regions: 50,
+        ..MapGenConfig::default()
+    };
+    assert!(generate(&too_many, &mut seeded_rng(0)).is_err());
+}
+
+#[test]
+fn bots_play_on_generated_maps() {
+    use arena::Match;
+    use game::load_setup;
+    use strategy::{PlannerConfig, PlannerStrategy, RandomStrategy, seeded_rng};
+    let config = MapGenConfig {
+        regions: 16,
+        super_regions: 4,
+        wastelands: 2,
+        ..MapGenConfig::default()
+    };
+    for seed in 0..3 {
+        let file = generate(&config, &mut seeded_rng(seed)).unwrap();
+        let mut setup = load_setup(&file.to_text()).unwrap();
+        setup.settings.max_rounds = 15;
+        let report = Match::new(setup, seed).play(Box::new(PlannerStrategy::new(PlannerConfig::default())), Box::new(RandomStrategy::new()));
+        assert_eq!(report.crashes, [None, None]);
+    }
+}
@@ -0,0 +1,340 @@
+//! Candidate move generation.
+//!
+//! Every combination of placements and attacks is far too many to look at,
+//! so turns are built from a few sensible pieces:
+//!
+//! * Placements only go on border regions, the ones with the best targets
+//!   first, and the income is split in `quanta` equal chunks between them.
+//! * Each region with armies to spare gets a handful of options of its own:
+//!   hold, attack a neighbour with a quantized share of its armies, or for an
+//!   interior region send everything towards the closest border. Attacks that
+//!   can't expect to take their target are dominated by holding and dropped.
+//! * Attack sets start with every region's best option and branch by
+//!   swapping one region at a time to its next best.
+//!
+//! `MoveGenConfig` bounds how many of each are produced.
+
+use combat;
+use map::{GameMap, OwnerValue};
+use moves::Move;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub struct MoveGenConfig {
+    /// Border regions considered for placements.
+    pub placement_regions: usize,
+    /// Placement distributions returned.
+    pub placement_breadth: usize,
+    /// Attack sets returned for each set of placements.
+    pub attack_breadth: usize,
+    /// Chunks armies are split into, both for placements and attacks.
+    pub quanta: u64
+}
+
+/// One way a single region can use its armies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionOption {
+    pub moves: Vec<Move>,
+    pub score: f64
+}
+
+impl Default for MoveGenConfig {
+    fn default() -> MoveGenConfig {
+        MoveGenConfig {
+            placement_regions: 3,
+            placement_breadth: 4,
+            attack_breadth: 3,
+            quanta: 2
+        }
+    }
+}
+
+/// Whole turns for `owner`, placements followed by attacks.
+pub fn turns(map: &GameMap, owner: OwnerValue, config: &MoveGenConfig) -> Vec<Vec<Move>> {
+    let mut turns = Vec::new();
+    for placement in placements(map, owner, map.income(owner), config) {
+        for attacks in attack_sets(map, owner, &placement, config) {
+            let mut turn = placement.clone();
+            turn.extend(attacks);
+            if !turns.contains(&turn) {
+                turns.push(turn);
+            }
+        }
+    }
+    if turns.is_empty() {
+        turns.push(Vec::new());
+    }
+    turns
+}
+
+/// Ways to spread `income` over `owner`'s most promising border regions.
+pub fn placements(map: &GameMap, owner: OwnerValue, income: u64, config: &MoveGenConfig) -> Vec<Vec<Move>> {
+    let regions: Vec<u64> = ranked_borders(map, owner).into_iter()
+        .take(config.placement_regions.max(1))
+        .collect();
+    if regions.is_empty() || income == 0 {
+        return vec![Vec::new()];
+    }
+
+    let quanta = config.quanta.max(1).min(income);
+    let mut distributions = Vec::new();
+    compositions(quanta, regions.len(), &mut Vec::new(), &mut distributions);
+    // Concentrated placements first, they are usually the ones that matter.
+    distributions.sort_by_key(|chunks: &Vec<u64>| {
+        let used = chunks.iter().filter(|chunk| **chunk > 0).count();
+        let first_used = chunks.iter().position(|chunk| *chunk > 0).unwrap_or(0);
+        (used, first_used)
+    });
+
+    distributions.into_iter().take(config.placement_breadth.max(1)).map(|chunks| {
+        let mut moves = Vec::new();
+        let mut left = income;
+        let last = chunks.iter().rposition(|chunk| *chunk > 0).unwrap();
+        for (index, chunk) in chunks.iter().enumerate() {
+            if *chunk == 0 {
+                continue;
+            }
+            let armies = if index == last {left} else {income * chunk / quanta};
+            left -= armies;
+            moves.push(Move::PlaceArmies(regions[index], armies));
+        }
+        moves
+    }).collect()
+}
+
+/// The options for a single region that can spend `available` armies, best
+/// first. Holding is always one of them.
+pub fn region_options(map: &GameMap, owner: OwnerValue, id: u64, available: u64, config: &MoveGenConfig) -> Vec<RegionOption> {
+    let mut options = vec![RegionOption {moves: Vec::new(), score: 0.0}];
+    if available == 0 {
+        return options;
+    }
+    let region = map.region(id).unwrap();
+
+    if !map.is_border(owner, id) {
+        if let Some(step) = step_towards_border(map, owner, id) {
+            options.push(RegionOption {
+                moves: vec![Move::AttackTransfer(id, step, available)],
+                score: 0.5
+            });
+        }
+    }
+
+    for neighbor_id in region.neighbor_ids.iter() {
+        let neighbor = map.region(*neighbor_id).unwrap();
+        if neighbor.owner == owner {
+            continue;
+        }
+        let needed = combat::armies_needed(neighbor.armies);
+        if needed > available {
+            continue;
+        }
+        for armies in quantized(needed, available, config.quanta) {
+            options.push(RegionOption {
+                moves: vec![Move::AttackTransfer(id, *neighbor_id, armies)],
+                score: target_value(map, owner, *neighbor_id) - 0.01 * (armies - needed) as f64
+            });
+        }
+    }
+
+    options.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+    options
+}
+
+/// Attack sets to go with `placements`, built from every region's options.
+pub fn attack_sets(map: &GameMap, owner: OwnerValue, placements: &[Move], config: &MoveGenConfig) -> Vec<Vec<Move>> {
+    let mut placed = HashMap::new();
+    for movement in placements {
+        if let Move::PlaceArmies(id, armies) = *movement {
+            *placed.entry(id).or_insert(0) += armies;
+        }
+    }
+
+    let groups: Vec<Vec<RegionOption>> = map.owned_by(owner).iter().map(|region| {
+        let available = (region.armies + placed.get(&region.id).cloned().unwrap_or(0)).saturating_sub(1);
+        region_options(map, owner, region.id, available, config)
+    }).filter(|options| options.len() > 1).collect();
+
+    let assemble = |choices: &[usize]| -> Vec<Move> {
+        let mut moves = Vec::new();
+        for (group, choice) in groups.iter().zip(choices.iter()) {
+            moves.extend(group[*choice].moves.iter().cloned());
+        }
+        moves
+    };
+
+    let best: Vec<usize> = groups.iter().map(|_| 0).collect();
+    let mut sets = vec![assemble(&best)];
+    // Regions whose second choice is closest to their first are the least
+    // sure about it, so they get to branch first.
+    let mut branches: Vec<(usize, f64)> = groups.iter().enumerate()
+        .map(|(index, options)| (index, options[0].score - options[1].score))
+        .collect();
+    branches.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap().then(a.0.cmp(&b.0)));
+    for (index, _) in branches {
+        if sets.len() >= config.attack_breadth.max(1) {
+            break;
+        }
+        let mut choices = best.clone();
+        choices[index] = 1;
+        let set = assemble(&choices);
+        if !sets.contains(&set) {
+            sets.push(set);
+        }
+    }
+    sets
+}
+
+/// `owner`'s border regions, the ones next to the most valuable target
+/// first.
+fn ranked_borders(map: &GameMap, owner: OwnerValue) -> Vec<u64> {
+    let mut borders: Vec<(u64, f64)> = map.owned_by(owner).iter()
+        .filter(|region| map.is_border(owner, region.id))
+        .map(|region| {
+            let best = region.neighbor_ids.iter()
+                .filter(|neighbor| map.region(**neighbor).unwrap().owner != owner)
+                .map(|neighbor| target_value(map, owner, *neighbor) / combat::armies_needed(map.region(*neighbor).unwrap().armies) as f64)
+                .fold(0.0, f64::max);
+            (region.id, best)
+        })
+        .collect();
+    borders.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+    borders.into_iter().map(|(id, _)| id).collect()
+}
+
+/// Rough worth of taking `id`: enemy regions are worth more than neutral
+/// ones and regions in a super region we mostly hold more still.
+fn target_value(map: &GameMap, owner: OwnerValue, id: u64) -> f64 {
+    let region = map.region(id).unwrap();
+    let mut value = if region.owner == OwnerValue::Neutral {1.0} else {1.5};
+    let super_region = map.super_region(region.super_region_id).unwrap();
+    let held = super_region.region_ids.iter()
+        .filter(|member| map.region(**member).unwrap().owner == owner)
+        .count();
+    if super_region.value > 0 {
+        value += held as f64 / super_region.region_ids.len() as f64;
+    }
+    value
+}
+
+/// The neighbour of interior region `id` on the shortest path to a border
+/// through `owner`'s regions.
+fn step_towards_border(map: &GameMap, owner: OwnerValue, id: u64) -> Option<u64> {
+    let borders: Vec<u64> = map.owned_by(owner).iter()
+        .filter(|region| map.is_border(owner, region.id))
+        .map(|region| region.id)
+        .collect();
+    let distances = map.distances(&borders);
+    map.region(id).unwrap().neighbor_ids.iter()
+        .filter(|neighbor| map.region(**neighbor).unwrap().owner == owner)
+        .filter_map(|neighbor| distances.get(neighbor).map(|distance| (*distance, *neighbor)))
+        .min()
+        .map(|(_, neighbor)| neighbor)
+}
+
+/// Amounts from `needed` up to `available`, `available` split in `quanta`
+/// steps with the ones below `needed` dropped.
+fn quantized(needed: u64, available: u64, quanta: u64) -> Vec<u64> {
+    let quanta = quanta.max(1);
+    let mut amounts = vec![needed];
+    for step in 1..quanta + 1 {
+        let armies = available * step / quanta;
+        if armies > needed && !amounts.contains(&armies) {
+            amounts.push(armies);
+        }
+    }
+    amounts
+}
+
+/// Every way to split `chunks` over `slots`.
+fn compositions(chunks: u64, slots: usize, current: &mut Vec<u64>, out: &mut Vec<Vec<u64>>) {
+    if current.len() + 1 == slots {
+        current.push(chunks);
+        out.push(current.clone());
+        current.pop();
+        return;
+    }
+    for chunk in (0..chunks + 1).rev() {
+        current.push(chunk);
+        compositions(chunks - chunk, slots, current, out);
+        current.pop();
+    }
+}
+
+#[cfg(test)]
+fn test_map() -> GameMap {
+    // 1 - 2 - 3 - 4 in one super region. We hold 1 and 2, 3 is neutral with 2
+    // armies and 4 is the enemy's with 8.
+    let mut map = GameMap::new();
+    map.add_super_region(1, 3);
+    for id in 1..5 {
+        map.add_region(id, 1);
+    }
+    for id in 1..4 {
+        map.add_region_neighbors(id, vec![id + 1]);
+    }
+    map.update_map(1, OwnerValue::Ally, 4);
+    map.update_map(2, OwnerValue::Ally, 3);
+    map.update_map(4, OwnerValue::Enemy, 8);
+    map
+}
+
+#[test]
+fn placements_go_on_borders() {
+    let map = test_map();
+    let placements = placements(&map, OwnerValue::Ally, 5, &MoveGenConfig::default());
+    assert_eq!(placements, vec![vec![Move::PlaceArmies(2, 5)]]);
+}
+
+#[test]
+fn placements_are_split_in_quanta() {
+    let mut map = test_map();
+    map.update_map(3, OwnerValue::Ally, 1);
+    let mut config = MoveGenConfig::default();
+    config.placement_breadth = 10;
+    let placements = placements(&map, OwnerValue::Ally, 5, &config);
+    assert_eq!(placements.len(), 1);
+    map.update_map(1, OwnerValue::Ally, 1);
+    map.add_super_region(2, 1);
+    map.add_region(5, 2);
+    map.add_region_neighbors(5, vec![1]);
+    let placements = self::placements(&map, OwnerValue::Ally, 5, &config);
+    // Two quanta over the two borders: all on either, or one each.
+    assert_eq!(placements, vec![
+        vec![Move::PlaceArmies(1, 5)],
+        vec![Move::PlaceArmies(3, 5)],
+        vec![Move::PlaceArmies(1, 2), Move::PlaceArmies(3, 3)]
+    ]);
+}
+
+#[test]
+fn hopeless_attacks_are_pruned() {
+    let map = test_map();
+    let options = region_options(&map, OwnerValue::Ally, 2, 2, &MoveGenConfig::default());
+    assert_eq!(options.len(), 1);
+    let options = region_options(&map, OwnerValue::Ally, 2, 7, &MoveGenConfig::default());
+    assert_eq!(options[0].moves, vec![Move::AttackTransfer(2, 3, 3)]);
+    assert!(options.iter().all(|option| option.moves.iter().all(|movement| *movement != Move::AttackTransfer(2, 4, 7))));
+}
+
+#[test]
+fn interior_armies_head_for_the_border() {
+    let map = test_map();
+    let options = region_options(&map, OwnerValue::Ally, 1, 3, &MoveGenConfig::default());
+    assert_eq!(options[0].moves, vec![Move::AttackTransfer(1, 2, 3)]);
+}
+
+#[test]
+fn turns_branch_on_attacks() {
+    let map = test_map();
+    let turns = turns(&map, OwnerValue::Ally, &MoveGenConfig::default());
+    assert_eq!(turns[0], vec![
+        Move::PlaceArmies(2, 5),
+        Move::AttackTransfer(1, 2, 3),
+        Move::AttackTransfer(2, 3, 3)
+    ]);
+    assert!(turns.len() > 1);
+    for turn in turns {
+        assert_eq!(turn[0], Move::PlaceArmies(2, 5));
+    }
+}
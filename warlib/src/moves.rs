@@ -0,0 +1,307 @@
+//! Moves the bot can issue and the rules they have to follow.
+//!
+//! The engine silently ignores illegal moves, so rather than losing armies to
+//! a bad order `validate_moves()` checks a turn before it is sent and
+//! `repair_moves()` trims or drops whatever would be rejected. Both take the
+//! moves already recorded on the map this round into account.
+
+use map::OwnerValue;
+use state::GameState;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Move {
+    /// Region id and armies to place there.
+    PlaceArmies(u64, u64),
+    /// Source region id, target region id and armies to send.
+    AttackTransfer(u64, u64, u64)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MoveError {
+    UnknownRegion(u64),
+    NotOwned(u64),
+    NotNeighbor(u64, u64),
+    NoArmies,
+    /// Armies placed so far this turn and the armies we were given.
+    TooManyPlaced(u64, u64),
+    /// Source region, armies requested and armies still available there.
+    NotEnoughArmies(u64, u64, u64)
+}
+
+/// Tracks placements and armies already spent while walking a turn in order,
+/// so each move is checked against what the moves before it left behind. It
+/// starts out with the moves the map has recorded for this round.
+pub struct MoveChecker<'a> {
+    state: &'a GameState,
+    placed: u64,
+    placed_per_region: HashMap<u64, u64>,
+    spent: HashMap<u64, u64>
+}
+
+impl Move {
+    /// Formats the move the way the engine expects it from the bot `name`.
+    pub fn to_command(&self, name: &str) -> String {
+        match *self {
+            Move::PlaceArmies(region, armies) => {
+                format!("{} place_armies {} {}", name, region, armies)
+            },
+            Move::AttackTransfer(source, target, armies) => {
+                format!("{} attack/transfer {} {} {}", name, source, target, armies)
+            }
+        }
+    }
+
+    pub fn armies(&self) -> u64 {
+        match *self {
+            Move::PlaceArmies(_, armies) => armies,
+            Move::AttackTransfer(_, _, armies) => armies
+        }
+    }
+
+    fn with_armies(&self, armies: u64) -> Move {
+        match *self {
+            Move::PlaceArmies(region, _) => Move::PlaceArmies(region, armies),
+            Move::AttackTransfer(source, target, _) => Move::AttackTransfer(source, target, armies)
+        }
+    }
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            MoveError::UnknownRegion(id) => write!(f, "region {} doesn't exist", id),
+            MoveError::NotOwned(id) => write!(f, "region {} isn't ours", id),
+            MoveError::NotNeighbor(source, target) => {
+                write!(f, "region {} doesn't border region {}", source, target)
+            },
+            MoveError::NoArmies => f.write_str("move has no armies"),
+            MoveError::TooManyPlaced(placed, allowed) => {
+                write!(f, "placed {} armies but only have {}", placed, allowed)
+            },
+            MoveError::NotEnoughArmies(source, requested, available) => {
+                write!(f, "region {} can't send {} armies, only {} available", source, requested, available)
+            }
+        }
+    }
+}
+
+impl<'a> MoveChecker<'a> {
+    pub fn new(state: &'a GameState) -> MoveChecker<'a> {
+        let mut checker = MoveChecker {
+            state: state,
+            placed: 0,
+            placed_per_region: HashMap::new(),
+            spent: HashMap::new()
+        };
+        for movement in state.map.issued() {
+            checker.commit(movement);
+        }
+        checker
+    }
+
+    /// Armies that could still leave `region_id`, one always has to stay
+    /// behind.
+    pub fn available(&self, region_id: u64) -> u64 {
+        let armies = match self.state.map.region(region_id) {
+            Some(region) => region.armies,
+            None => return 0
+        };
+        let placed = self.placed_per_region.get(&region_id).cloned().unwrap_or(0);
+        let spent = self.spent.get(&region_id).cloned().unwrap_or(0);
+        (armies + placed).saturating_sub(1 + spent)
+    }
+
+    /// Armies left to place this turn.
+    pub fn unplaced(&self) -> u64 {
+        self.state.settings.starting_armies.saturating_sub(self.placed)
+    }
+
+    pub fn check(&self, movement: &Move) -> Result<(), MoveError> {
+        if movement.armies() == 0 {
+            return Err(MoveError::NoArmies);
+        }
+        match *movement {
+            Move::PlaceArmies(region_id, armies) => {
+                try!(self.check_owned(region_id));
+                let allowed = self.state.settings.starting_armies;
+                if self.placed + armies > allowed {
+                    return Err(MoveError::TooManyPlaced(self.placed + armies, allowed));
+                }
+            },
+            Move::AttackTransfer(source, target, armies) => {
+                try!(self.check_owned(source));
+                if self.state.map.region(target).is_none() {
+                    return Err(MoveError::UnknownRegion(target));
+                }
+                if !self.state.map.region(source).unwrap().neighbor_ids.contains(&target) {
+                    return Err(MoveError::NotNeighbor(source, target));
+                }
+                let available = self.available(source);
+                if armies > available {
+                    return Err(MoveError::NotEnoughArmies(source, armies, available));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records a move as issued, it is not checked first.
+    pub fn commit(&mut self, movement: &Move) {
+        match *movement {
+            Move::PlaceArmies(region_id, armies) => {
+                self.placed += armies;
+                *self.placed_per_region.entry(region_id).or_insert(0) += armies;
+            },
+            Move::AttackTransfer(source, _, armies) => {
+                *self.spent.entry(source).or_insert(0) += armies;
+            }
+        }
+    }
+
+    fn check_owned(&self, region_id: u64) -> Result<(), MoveError> {
+        match self.state.map.region(region_id) {
+            None => Err(MoveError::UnknownRegion(region_id)),
+            Some(region) => if region.owner == OwnerValue::Ally {
+                Ok(())
+            } else {
+                Err(MoveError::NotOwned(region_id))
+            }
+        }
+    }
+}
+
+/// Checks the rest of a turn in order, returning every offending move's index
+/// along with why it would be rejected.
+pub fn validate_moves(state: &GameState, moves: &[Move]) -> Result<(), Vec<(usize, MoveError)>> {
+    let mut checker = MoveChecker::new(state);
+    let mut errors = Vec::new();
+    for (index, movement) in moves.iter().enumerate() {
+        match checker.check(movement) {
+            Ok(()) => checker.commit(movement),
+            Err(e) => errors.push((index, e))
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Makes `moves` legal given the moves already issued this turn. Moves asking
+/// for too many armies are cut down to what is left, anything else illegal is
+/// dropped. Each change is logged as a warning.
+pub fn repair_moves(state: &GameState, moves: Vec<Move>) -> Vec<Move> {
+    let mut checker = MoveChecker::new(state);
+
+    let mut repaired = Vec::with_capacity(moves.len());
+    for movement in moves {
+        let fixed = match checker.check(&movement) {
+            Ok(()) => Some(movement),
+            Err(MoveError::TooManyPlaced(_, _)) => {
+                let left = checker.unplaced();
+                warn!("{:?}: too many armies placed, trimming to {}", movement, left);
+                if left > 0 {Some(movement.with_armies(left))} else {None}
+            },
+            Err(MoveError::NotEnoughArmies(source, _, available)) => {
+                warn!("{:?}: region {} only has {} armies available", movement, source, available);
+                if available > 0 {Some(movement.with_armies(available))} else {None}
+            },
+            Err(e) => {
+                warn!("{:?}: dropping, {}", movement, e);
+                None
+            }
+        };
+        if let Some(movement) = fixed {
+            checker.commit(&movement);
+            repaired.push(movement);
+        }
+    }
+    repaired
+}
+
+#[cfg(test)]
+fn test_state() -> GameState {
+    // 1 - 2 - 3, we own 1 with 3 armies and 2 with 1, 3 is neutral.
+    let mut state = GameState::new();
+    state.settings.starting_armies = 5;
+    state.map.add_super_region(1, 2);
+    for id in 1..4 {
+        state.map.add_region(id, 1);
+    }
+    state.map.add_region_neighbors(1, vec![2]);
+    state.map.add_region_neighbors(2, vec![3]);
+    state.map.update_map(1, OwnerValue::Ally, 3);
+    state.map.update_map(2, OwnerValue::Ally, 1);
+    state
+}
+
+#[test]
+fn validate_legal_turn() {
+    let state = test_state();
+    let moves = vec![
+        Move::PlaceArmies(2, 4),
+        Move::PlaceArmies(1, 1),
+        Move::AttackTransfer(2, 3, 4),
+        Move::AttackTransfer(1, 2, 3)
+    ];
+    assert_eq!(validate_moves(&state, &moves), Ok(()));
+}
+
+#[test]
+fn validate_too_many_placed() {
+    let state = test_state();
+    let moves = vec![Move::PlaceArmies(1, 3), Move::PlaceArmies(2, 3)];
+    assert_eq!(validate_moves(&state, &moves), Err(vec![(1, MoveError::TooManyPlaced(6, 5))]));
+}
+
+#[test]
+fn validate_ownership_and_adjacency() {
+    let state = test_state();
+    let moves = vec![
+        Move::PlaceArmies(3, 1),
+        Move::AttackTransfer(3, 2, 1),
+        Move::AttackTransfer(1, 3, 1),
+        Move::AttackTransfer(1, 9, 1)
+    ];
+    assert_eq!(validate_moves(&state, &moves), Err(vec![
+        (0, MoveError::NotOwned(3)),
+        (1, MoveError::NotOwned(3)),
+        (2, MoveError::NotNeighbor(1, 3)),
+        (3, MoveError::UnknownRegion(9))
+    ]));
+}
+
+#[test]
+fn validate_double_spending() {
+    let state = test_state();
+    let moves = vec![
+        Move::AttackTransfer(1, 2, 2),
+        Move::AttackTransfer(1, 2, 1),
+        Move::AttackTransfer(2, 3, 1)
+    ];
+    assert_eq!(validate_moves(&state, &moves), Err(vec![
+        (1, MoveError::NotEnoughArmies(1, 1, 0)),
+        (2, MoveError::NotEnoughArmies(2, 1, 0))
+    ]));
+}
+
+#[test]
+fn repair_trims_and_drops() {
+    let mut state = test_state();
+    state.map.record_move(Move::PlaceArmies(1, 4));
+    let moves = vec![
+        Move::PlaceArmies(2, 3),
+        Move::PlaceArmies(2, 1),
+        Move::AttackTransfer(1, 3, 2),
+        Move::AttackTransfer(1, 2, 9),
+        Move::AttackTransfer(2, 3, 1)
+    ];
+    assert_eq!(repair_moves(&state, moves), vec![
+        Move::PlaceArmies(2, 1),
+        Move::AttackTransfer(1, 2, 6),
+        Move::AttackTransfer(2, 3, 1)
+    ]);
+}
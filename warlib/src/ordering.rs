@@ -0,0 +1,175 @@
+//! The order our moves go out in.
+//!
+//! The engine plays the attack/transfer moves of both players in turns, one
+//! of ours then one of theirs, in the order each bot sent them. When a move
+//! goes out matters: reinforcements should land before the enemy gets to
+//! attack the regions they go to, probes should wear a target down before
+//! the strike that takes it, and armies leaving a region next to the enemy
+//! should stay as long as they can.
+//!
+//! Every move is put in one of the `MoveKind`s and an `OrderingPolicy` says
+//! which kinds go first. Moves of the same kind keep the order the strategy
+//! gave them, which keeps the waves of an attack in order, and placements
+//! always stay in front.
+
+use combat::armies_needed;
+use map::{GameMap, OwnerValue};
+use moves::Move;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveKind {
+    /// A transfer out of a region that doesn't border the enemy.
+    Reinforcement,
+    /// An attack too small to take its target alone.
+    Probe,
+    /// An attack big enough to take its target alone.
+    Strike,
+    /// A transfer out of a region bordering the enemy.
+    DefensiveTransfer
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderingPolicy {
+    /// Kinds in the order they go out. Kinds left out go last, in the order
+    /// they were given.
+    pub order: Vec<MoveKind>
+}
+
+impl OrderingPolicy {
+    /// Attacks and transfers go out as the strategy gave them.
+    pub fn as_issued() -> OrderingPolicy {
+        OrderingPolicy {
+            order: Vec::new()
+        }
+    }
+
+    /// Reinforcements first, then probes and strikes, defensive transfers
+    /// last.
+    pub fn staged() -> OrderingPolicy {
+        OrderingPolicy {
+            order: vec![
+                MoveKind::Reinforcement,
+                MoveKind::Probe,
+                MoveKind::Strike,
+                MoveKind::DefensiveTransfer
+            ]
+        }
+    }
+
+    fn rank(&self, kind: Option<MoveKind>) -> usize {
+        match kind {
+            None => 0,
+            Some(kind) => 1 + self.order.iter().position(|other| *other == kind).unwrap_or(self.order.len())
+        }
+    }
+}
+
+impl Default for OrderingPolicy {
+    fn default() -> OrderingPolicy {
+        OrderingPolicy::staged()
+    }
+}
+
+/// What kind of move `movement` is on `map`, `None` for placements.
+pub fn kind(map: &GameMap, movement: &Move) -> Option<MoveKind> {
+    match *movement {
+        Move::PlaceArmies(_, _) => None,
+        Move::AttackTransfer(source, target, armies) => {
+            let target = match map.region(target) {
+                Some(region) => region,
+                None => return Some(MoveKind::Strike)
+            };
+            if target.owner == OwnerValue::Ally {
+                if borders_enemy(map, source) {
+                    Some(MoveKind::DefensiveTransfer)
+                } else {
+                    Some(MoveKind::Reinforcement)
+                }
+            } else if armies < armies_needed(target.armies) {
+                Some(MoveKind::Probe)
+            } else {
+                Some(MoveKind::Strike)
+            }
+        }
+    }
+}
+
+/// `moves` sorted by `policy`.
+pub fn order_moves(map: &GameMap, moves: Vec<Move>, policy: &OrderingPolicy) -> Vec<Move> {
+    let mut ranked: Vec<(usize, Move)> = moves.into_iter()
+        .map(|movement| (policy.rank(kind(map, &movement)), movement))
+        .collect();
+    ranked.sort_by_key(|&(rank, _)| rank);
+    ranked.into_iter().map(|(_, movement)| movement).collect()
+}
+
+fn borders_enemy(map: &GameMap, id: u64) -> bool {
+    map.region(id).map_or(false, |region| {
+        region.neighbor_ids.iter().any(|neighbor| map.region(*neighbor).unwrap().owner == OwnerValue::Enemy)
+    })
+}
+
+#[cfg(test)]
+fn test_map() -> GameMap {
+    // 1 - 2 - 3 - 4 - 5, we hold 1 to 3, 4 is neutral and 5 the enemy's,
+    // with 2 - 5 as well.
+    let mut map = GameMap::new();
+    map.add_super_region(1, 2);
+    for id in 1..6 {
+        map.add_region(id, 1);
+    }
+    for id in 1..5 {
+        map.add_region_neighbors(id, vec![id + 1]);
+    }
+    map.add_region_neighbors(2, vec![5]);
+    for id in 1..4 {
+        map.update_map(id, OwnerValue::Ally, 10);
+    }
+    map.update_map(4, OwnerValue::Neutral, 5);
+    map.update_map(5, OwnerValue::Enemy, 5);
+    map
+}
+
+#[test]
+fn moves_are_classified() {
+    let map = test_map();
+    assert_eq!(kind(&map, &Move::PlaceArmies(1, 2)), None);
+    assert_eq!(kind(&map, &Move::AttackTransfer(1, 2, 9)), Some(MoveKind::Reinforcement));
+    assert_eq!(kind(&map, &Move::AttackTransfer(2, 3, 9)), Some(MoveKind::DefensiveTransfer));
+    assert_eq!(kind(&map, &Move::AttackTransfer(3, 4, 4)), Some(MoveKind::Probe));
+    assert_eq!(kind(&map, &Move::AttackTransfer(3, 4, 8)), Some(MoveKind::Strike));
+}
+
+#[test]
+fn staged_policy_sorts_by_kind() {
+    let map = test_map();
+    let moves = vec![
+        Move::AttackTransfer(2, 3, 9),
+        Move::AttackTransfer(3, 4, 8),
+        Move::AttackTransfer(2, 5, 4),
+        Move::AttackTransfer(3, 4, 1),
+        Move::AttackTransfer(1, 2, 9),
+        Move::PlaceArmies(3, 5)
+    ];
+    assert_eq!(order_moves(&map, moves.clone(), &OrderingPolicy::staged()), vec![
+        Move::PlaceArmies(3, 5),
+        Move::AttackTransfer(1, 2, 9),
+        Move::AttackTransfer(2, 5, 4),
+        Move::AttackTransfer(3, 4, 1),
+        Move::AttackTransfer(3, 4, 8),
+        Move::AttackTransfer(2, 3, 9)
+    ]);
+    let mut placements_first = vec![Move::PlaceArmies(3, 5)];
+    placements_first.extend(moves[..5].iter().cloned());
+    assert_eq!(order_moves(&map, moves, &OrderingPolicy::as_issued()), placements_first);
+}
+
+#[test]
+fn unlisted_kinds_go_last() {
+    let map = test_map();
+    let policy = OrderingPolicy {
+        order: vec![MoveKind::Strike]
+    };
+    let moves = vec![Move::AttackTransfer(1, 2, 9), Move::AttackTransfer(3, 4, 8)];
+    assert_eq!(order_moves(&map, moves, &policy), vec![Move::AttackTransfer(3, 4, 8), Move::AttackTransfer(1, 2, 9)]);
+}
@@ -0,0 +1,315 @@
+//! Choosing starting regions.
+//!
+//! Every candidate is scored on the super region it sits in: how much bonus
+//! it pays per neutral army that has to be beaten, how many of its regions
+//! border other super regions and how many wastelands it contains. On top of
+//! that candidates close to our other picks are favoured and ones close to
+//! the opponent's picks avoided.
+//!
+//! Picks go in snake order, the first player picks once and from then on
+//! each player picks twice in a row. With `plan_ahead` each candidate is
+//! judged by playing out the rest of the picking phase in that order,
+//! assuming the opponent always takes the best region left for them.
+
+use map::GameMap;
+use state::GameState;
+
+#[derive(Debug, Clone)]
+pub struct PickOptions {
+    /// Subtracted for the fraction of the super region bordering others.
+    pub border_weight: f64,
+    /// Subtracted for every wasteland in the super region.
+    pub wasteland_weight: f64,
+    /// Added for every one of our picks within `near_distance`.
+    pub synergy_weight: f64,
+    /// Subtracted for every opponent pick within `near_distance`.
+    pub enemy_weight: f64,
+    pub near_distance: u64,
+    /// Judge candidates by playing out the remaining picks. The opponent is
+    /// modelled as greedy so this is off by default.
+    pub plan_ahead: bool
+}
+
+/// What we have learned during the picking phase. The engine only sends the
+/// regions still on offer, so whatever vanished between two offers without us
+/// picking it was taken by the opponent.
+#[derive(Debug)]
+pub struct PickHistory {
+    pub ours: Vec<u64>,
+    pub theirs: Vec<u64>,
+    last_offered: Vec<u64>
+}
+
+impl Default for PickOptions {
+    fn default() -> PickOptions {
+        PickOptions {
+            border_weight: 0.1,
+            wasteland_weight: 0.1,
+            synergy_weight: 0.05,
+            enemy_weight: 0.1,
+            near_distance: 2,
+            plan_ahead: false
+        }
+    }
+}
+
+impl PickHistory {
+    pub fn new() -> PickHistory {
+        PickHistory {
+            ours: Vec::new(),
+            theirs: Vec::new(),
+            last_offered: Vec::new()
+        }
+    }
+
+    /// Updates the opponent's picks from a new offer, `starting_regions` is
+    /// what was on offer before the first pick.
+    pub fn observe(&mut self, starting_regions: &[u64], offered: &[u64]) {
+        let previous = if self.last_offered.is_empty() {
+            starting_regions.to_vec()
+        } else {
+            self.last_offered.clone()
+        };
+        for id in previous {
+            if !offered.contains(&id) && !self.ours.contains(&id) && !self.theirs.contains(&id) {
+                self.theirs.push(id);
+            }
+        }
+        self.last_offered = offered.to_vec();
+    }
+
+    pub fn record(&mut self, id: u64) {
+        self.ours.push(id);
+    }
+}
+
+/// Scores a single candidate given both players' picks so far.
+pub fn pick_value(map: &GameMap, id: u64, ours: &[u64], theirs: &[u64], options: &PickOptions) -> f64 {
+    if map.region(id).is_none() {
+        return 0.0;
+    }
+    let distances = map.distances(&[id]);
+    let near = |picks: &[u64]| picks.iter().filter(|pick| {
+        **pick != id && distances.get(pick).map_or(false, |distance| *distance <= options.near_distance)
+    }).count() as f64;
+
+    super_region_value(map, id, options)
+        + options.synergy_weight * near(ours)
+        - options.enemy_weight * near(theirs)
+}
+
+/// The part of a candidate's score that comes from its super region alone.
+fn super_region_value(map: &GameMap, id: u64, options: &PickOptions) -> f64 {
+    let region = map.region(id).unwrap();
+    let super_region = map.super_region(region.super_region_id).unwrap();
+
+    let mut armies = 0;
+    let mut borders = 0;
+    let mut wastelands = 0;
+    for member_id in super_region.region_ids.iter() {
+        let member = map.region(*member_id).unwrap();
+        armies += member.armies;
+        if member.wasteland {
+            wastelands += 1;
+        }
+        let outside = member.neighbor_ids.iter().any(|neighbor| {
+            map.region(*neighbor).unwrap().super_region_id != super_region.id
+        });
+        if outside {
+            borders += 1;
+        }
+    }
+
+    let size = super_region.region_ids.len().max(1) as f64;
+    let efficiency = super_region.value as f64 / armies.max(1) as f64;
+
+    efficiency
+        - options.border_weight * borders as f64 / size
+        - options.wasteland_weight * wastelands as f64
+}
+
+/// Scores every offered region, best first.
+pub fn evaluate_picks(state: &GameState, history: &PickHistory, offered: &[u64], options: &PickOptions) -> Vec<(u64, f64)> {
+    let remaining = remaining_picks(state, history);
+    let mut scores: Vec<(u64, f64, f64)> = offered.iter().map(|id| {
+        let immediate = pick_value(&state.map, *id, &history.ours, &history.theirs, options);
+        let value = if options.plan_ahead {
+            plan_value(&state.map, *id, offered, history, remaining, options)
+        } else {
+            immediate
+        };
+        (*id, value, immediate)
+    }).collect();
+    // Orders that end in the same picks are settled by taking the best
+    // region first, it might not come back around.
+    scores.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1).unwrap()
+            .then(b.2.partial_cmp(&a.2).unwrap())
+            .then(a.0.cmp(&b.0))
+    });
+    scores.into_iter().map(|(id, value, _)| (id, value)).collect()
+}
+
+/// The whole pick sequence we expect to end up with if we take the best
+/// offered region now, opponent picks included.
+pub fn plan_picks(state: &GameState, history: &PickHistory, offered: &[u64], options: &PickOptions) -> Vec<u64> {
+    let first = match evaluate_picks(state, history, offered, options).first() {
+        Some(&(id, _)) => id,
+        None => return Vec::new()
+    };
+    let remaining = remaining_picks(state, history);
+    let mut ours = history.ours.clone();
+    let mut theirs = history.theirs.clone();
+    let mut left: Vec<u64> = offered.to_vec();
+    play_out(&state.map, first, &mut left, &mut ours, &mut theirs, remaining, options);
+    ours.split_off(history.ours.len())
+}
+
+/// The region to take now. With `plan_ahead` the picks we expect to end up
+/// with are logged along with it.
+pub fn choose_pick(state: &GameState, history: &PickHistory, offered: &[u64], options: &PickOptions) -> Option<u64> {
+    if options.plan_ahead {
+        let plan = plan_picks(state, history, offered, options);
+        info!("planning to pick {:?}", plan);
+        return plan.first().cloned();
+    }
+    evaluate_picks(state, history, offered, options).first().map(|&(id, _)| id)
+}
+
+fn remaining_picks(state: &GameState, history: &PickHistory) -> usize {
+    (state.settings.starting_pick_amount as usize).saturating_sub(history.ours.len()).max(1)
+}
+
+fn plan_value(map: &GameMap, first: u64, offered: &[u64], history: &PickHistory, remaining: usize, options: &PickOptions) -> f64 {
+    let mut ours = history.ours.clone();
+    let mut theirs = history.theirs.clone();
+    let mut left = offered.to_vec();
+    let ours_before = ours.len();
+    let theirs_before = theirs.len();
+    play_out(map, first, &mut left, &mut ours, &mut theirs, remaining, options);
+
+    // Proximity already steered the picks made along the way, the outcome is
+    // judged on the super regions each side ends up in. What the opponent
+    // gets out of our choice counts against it, taking a slightly worse
+    // region can be right if it denies them a better one.
+    let gained: f64 = ours[ours_before..].iter()
+        .map(|id| super_region_value(map, *id, options))
+        .sum();
+    let conceded: f64 = theirs[theirs_before..].iter()
+        .map(|id| super_region_value(map, *id, options))
+        .sum();
+    gained - conceded
+}
+
+/// Whether the pick at `index` in the whole picking phase goes to the player
+/// who picked first.
+pub fn first_player_picks(index: usize) -> bool {
+    ((index + 1) / 2) % 2 == 0
+}
+
+fn play_out(map: &GameMap, first: u64, left: &mut Vec<u64>, ours: &mut Vec<u64>, theirs: &mut Vec<u64>, remaining: usize, options: &PickOptions) {
+    let mut index = ours.len() + theirs.len();
+    let we_are_first = first_player_picks(index);
+    take(left, ours, first);
+    let mut ours_left = remaining - 1;
+    let mut theirs_left = remaining;
+    while (ours_left > 0 || theirs_left > 0) && !left.is_empty() {
+        index += 1;
+        if first_player_picks(index) == we_are_first {
+            if ours_left > 0 {
+                let best = best_pick(map, left, ours, theirs, options);
+                take(left, ours, best);
+                ours_left -= 1;
+            }
+        } else if theirs_left > 0 {
+            let best = best_pick(map, left, theirs, ours, options);
+            take(left, theirs, best);
+            theirs_left -= 1;
+        }
+    }
+}
+
+fn best_pick(map: &GameMap, left: &[u64], mine: &[u64], other: &[u64], options: &PickOptions) -> u64 {
+    let mut best = left[0];
+    let mut best_value = ::std::f64::MIN;
+    for id in left {
+        let value = pick_value(map, *id, mine, other, options);
+        if value > best_value {
+            best = *id;
+            best_value = value;
+        }
+    }
+    best
+}
+
+fn take(left: &mut Vec<u64>, picks: &mut Vec<u64>, id: u64) {
+    left.retain(|left_id| *left_id != id);
+    picks.push(id);
+}
+
+#[cfg(test)]
+fn test_state() -> GameState {
+    // Super region 1 is worth 4 over two plain regions, super region 2 is
+    // worth 4 over two regions one of which is a wasteland and super region 3
+    // is worth nothing. They sit in a line 1 - 2 - 3 - 4 - 5 - 6.
+    let mut state = GameState::new();
+    state.settings.starting_pick_amount = 1;
+    state.map.add_super_region(1, 4);
+    state.map.add_super_region(2, 4);
+    state.map.add_super_region(3, 0);
+    for (id, super_region) in vec![(1, 1), (2, 1), (3, 2), (4, 2), (5, 3), (6, 3)] {
+        state.map.add_region(id, super_region);
+    }
+    for id in 1..6 {
+        state.map.add_region_neighbors(id, vec![id + 1]);
+    }
+    state.map.upgrade_to_wasteland(4);
+    state
+}
+
+#[test]
+fn prefers_cheap_super_regions() {
+    let state = test_state();
+    let history = PickHistory::new();
+    assert_eq!(choose_pick(&state, &history, &[1, 3, 5], &PickOptions::default()), Some(1));
+    let scores = evaluate_picks(&state, &history, &[1, 3, 5], &PickOptions::default());
+    assert_eq!(scores.iter().map(|&(id, _)| id).collect::<Vec<_>>(), vec![1, 3, 5]);
+}
+
+#[test]
+fn zero_armies_do_not_divide_by_zero() {
+    let mut state = test_state();
+    state.map.update_map(1, ::map::OwnerValue::Neutral, 0);
+    state.map.update_map(2, ::map::OwnerValue::Neutral, 0);
+    let value = pick_value(&state.map, 1, &[], &[], &PickOptions::default());
+    assert!(value.is_finite());
+}
+
+#[test]
+fn history_spots_opponent_picks() {
+    let mut history = PickHistory::new();
+    history.observe(&[1, 2, 3, 4], &[2, 3, 4]);
+    history.record(2);
+    history.observe(&[1, 2, 3, 4], &[4]);
+    assert_eq!(history.ours, vec![2]);
+    assert_eq!(history.theirs, vec![1, 3]);
+}
+
+#[test]
+fn picks_go_in_snake_order() {
+    let order: Vec<bool> = (0..8).map(first_player_picks).collect();
+    assert_eq!(order, vec![true, false, false, true, true, false, false, true]);
+}
+
+#[test]
+fn plans_the_whole_sequence() {
+    let mut state = test_state();
+    state.settings.starting_pick_amount = 2;
+    let history = PickHistory::new();
+    let mut options = PickOptions::default();
+    options.plan_ahead = true;
+    let plan = plan_picks(&state, &history, &[1, 2, 3, 5], &options);
+    assert_eq!(plan.len(), 2);
+    assert_eq!(plan[0], 1);
+    assert_eq!(choose_pick(&state, &history, &[1, 2, 3, 5], &options), Some(1));
+}
@@ -0,0 +1,188 @@
+//! Telling strategies apart from the games they play.
+//!
+//! Results between two players are kept as a `Score`. Its share of the
+//! points, draws counting half, comes with a Wilson score interval, which
+//! stays sensible for the small and lopsided samples a few dozen games give.
+//!
+//! Ratings are fitted to all the scores at once with the Bradley-Terry model
+//! rather than updated game by game like Elo, so the order the games finished
+//! in doesn't matter. They are given on the Elo scale: 400 points between two
+//! players means the stronger is expected to score ten times as much.
+
+use std::f64;
+
+/// Rating the average player is given.
+pub const MEAN_RATING: f64 = 1500.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Score {
+    pub wins: u64,
+    pub draws: u64,
+    pub losses: u64
+}
+
+impl Score {
+    pub fn games(&self) -> u64 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+
+    /// Share of the points, a half when nothing has been played.
+    pub fn rate(&self) -> f64 {
+        if self.games() == 0 {
+            0.5
+        } else {
+            self.points() / self.games() as f64
+        }
+    }
+
+    /// The same games seen from the other side.
+    pub fn reversed(&self) -> Score {
+        Score {
+            wins: self.losses,
+            draws: self.draws,
+            losses: self.wins
+        }
+    }
+
+    pub fn add(&mut self, other: &Score) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+}
+
+/// The Wilson score interval around `score.rate()`, `z` is 1.96 for 95%.
+pub fn wilson_interval(score: &Score, z: f64) -> (f64, f64) {
+    let n = score.games() as f64;
+    if n == 0.0 {
+        return (0.0, 1.0);
+    }
+    let p = score.rate();
+    let z2 = z * z;
+    let centre = p + z2 / (2.0 * n);
+    let spread = z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    let scale = 1.0 + z2 / n;
+    (((centre - spread) / scale).max(0.0), ((centre + spread) / scale).min(1.0))
+}
+
+/// Rating difference that makes `rate` the expected share of the points,
+/// infinite for 0 and 1.
+pub fn elo_difference(rate: f64) -> f64 {
+    if rate <= 0.0 {
+        f64::NEG_INFINITY
+    } else if rate >= 1.0 {
+        f64::INFINITY
+    } else {
+        -400.0 * (1.0 / rate - 1.0).log10()
+    }
+}
+
+/// Ratings for every player given `scores[i][j]`, player i's score against
+/// player j, averaging `MEAN_RATING`. Every game is in the matrix twice, once
+/// from either side, so `scores[j][i]` is always `scores[i][j].reversed()`
+/// and only the one is read.
+///
+/// Every pairing that was played counts one extra draw, without it a player
+/// who never lost would be rated infinitely high. Players who played nobody
+/// get the mean.
+pub fn fit_ratings(scores: &[Vec<Score>]) -> Vec<f64> {
+    let players = scores.len();
+    let played = |i: usize, j: usize| {
+        let games = scores[i][j].games();
+        if games == 0 {0.0} else {games as f64 + 1.0}
+    };
+    let points = |i: usize, j: usize| {
+        if played(i, j) == 0.0 {
+            0.0
+        } else {
+            scores[i][j].points() + 0.5
+        }
+    };
+
+    // Minorization-maximization, see Hunter (2004).
+    let mut strengths = vec![1.0; players];
+    for _ in 0..1000 {
+        let mut next = strengths.clone();
+        let mut change: f64 = 0.0;
+        for i in 0..players {
+            let won: f64 = (0..players).filter(|j| *j != i).map(|j| points(i, j)).sum();
+            let expected: f64 = (0..players)
+                .filter(|j| *j != i)
+                .map(|j| played(i, j) / (strengths[i] + strengths[j]))
+                .sum();
+            if expected > 0.0 {
+                next[i] = won / expected;
+            }
+        }
+        // Only the ratios matter, keep the geometric mean at 1.
+        let mean = (next.iter().map(|strength| strength.ln()).sum::<f64>() / players.max(1) as f64).exp();
+        for i in 0..players {
+            next[i] /= mean;
+            change = change.max((next[i] - strengths[i]).abs());
+        }
+        strengths = next;
+        if change < 1e-9 {
+            break;
+        }
+    }
+    strengths.iter().map(|strength| MEAN_RATING + 400.0 * strength.log10()).collect()
+}
+
+#[cfg(test)]
+fn score(wins: u64, draws: u64, losses: u64) -> Score {
+    Score {
+        wins: wins,
+        draws: draws,
+        losses: losses
+    }
+}
+
+#[test]
+fn intervals_hold_the_rate() {
+    let small = wilson_interval(&score(7, 2, 3), 1.96);
+    let large = wilson_interval(&score(70, 20, 30), 1.96);
+    let rate = score(7, 2, 3).rate();
+    assert!(small.0 < rate && rate < small.1);
+    assert!(small.0 < large.0 && large.1 < small.1);
+    let perfect = wilson_interval(&score(10, 0, 0), 1.96);
+    assert_eq!(perfect.1, 1.0);
+    assert!(perfect.0 > 0.6);
+}
+
+#[test]
+fn elo_differences_match_the_expected_score() {
+    assert_eq!(elo_difference(0.5), 0.0);
+    assert!((elo_difference(10.0 / 11.0) - 400.0).abs() < 1e-9);
+    assert_eq!(elo_difference(0.0), f64::NEG_INFINITY);
+}
+
+#[test]
+fn ratings_follow_the_results() {
+    // Filled in from both sides the way the tournament does.
+    let results = |players: usize, pairings: &[(usize, usize, Score)]| {
+        let mut scores = vec![vec![Score::default(); players]; players];
+        for &(i, j, score) in pairings.iter() {
+            scores[i][j] = score;
+            scores[j][i] = score.reversed();
+        }
+        scores
+    };
+
+    // 0 beats 1 beats 2 most of the time, 2 never played 0.
+    let scores = results(3, &[(0, 1, score(8, 0, 2)), (1, 2, score(8, 0, 2))]);
+    let ratings = fit_ratings(&scores);
+    assert!(ratings[0] > ratings[1] && ratings[1] > ratings[2]);
+    assert!((ratings.iter().sum::<f64>() / 3.0 - MEAN_RATING).abs() < 1e-6);
+
+    let even = results(2, &[(0, 1, score(3, 4, 3))]);
+    let ratings = fit_ratings(&even);
+    assert!((ratings[0] - ratings[1]).abs() < 1e-6);
+
+    // 3 wins out of 3 with the extra draw is 3.5 out of 4, 7 to 1.
+    let ratings = fit_ratings(&results(2, &[(0, 1, score(3, 0, 0))]));
+    assert!((ratings[0] - ratings[1] - 400.0 * 7f64.log10()).abs() < 1e-6);
+}
@@ -0,0 +1,405 @@
+//! Recording the games the bot plays.
+//!
+//! A replay is a JSON-lines file, one record per line, written as the game
+//! goes so whatever happened before a crash or a kill is kept. Every record
+//! has a `type` and `at`, the milliseconds since the game started:
+//!
+//! ```text
+//! {"type":"start","at":0,"version":1,"seed":42}
+//! {"type":"received","at":10,"line":"go place_armies 10000"}
+//! {"type":"sent","at":13,"took":3,"line":"player1 place_armies 4 5"}
+//! {"type":"map","at":10,"round":1,"regions":[[1,"ally",4],[2,"neutral",2]]}
+//! ```
+//!
+//! `received` and `sent` are the lines from and to the engine, `took` is how
+//! long answering took. `map` records are only written when asked for, with
+//! every region as we see it when the engine asks for placements.
+//!
+//! `VERSION` goes up whenever a record changes in a way readers have to know
+//! about. `read_record()` reads the records back, refusing replays from a
+//! newer version.
+
+use map::{GameMap, OwnerValue};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+pub const VERSION: u64 = 1;
+
+/// A line of a replay read back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Record {
+    Start { at: u64, version: u64, seed: u64 },
+    Received { at: u64, line: String },
+    Sent { at: u64, took: u64, line: String },
+    Map { at: u64, round: u64, regions: Vec<(u64, OwnerValue, u64)> }
+}
+
+/// Just enough JSON for the records above.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    /// Kept as written, seeds are `u64`s that an `f64` can't hold.
+    Number(String),
+    Text(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+    Other
+}
+
+pub struct ReplayWriter<W: Write> {
+    out: W,
+    started: Instant,
+    /// Whether map snapshots are written.
+    pub snapshots: bool,
+    failed: bool
+}
+
+impl ReplayWriter<Box<Write + Send>> {
+    /// A replay written to the file at `path`, replacing whatever is there.
+    pub fn create(path: &str, snapshots: bool) -> io::Result<ReplayWriter<Box<Write + Send>>> {
+        let file = try!(File::create(path));
+        Ok(ReplayWriter::new(Box::new(BufWriter::new(file)) as Box<Write + Send>, snapshots))
+    }
+}
+
+impl<W: Write> ReplayWriter<W> {
+    pub fn new(out: W, snapshots: bool) -> ReplayWriter<W> {
+        ReplayWriter {
+            out: out,
+            started: Instant::now(),
+            snapshots: snapshots,
+            failed: false
+        }
+    }
+
+    pub fn start(&mut self, seed: u64) {
+        let record = format!("{{\"type\":\"start\",\"at\":{},\"version\":{},\"seed\":{}}}", self.at(), VERSION, seed);
+        self.write(record);
+    }
+
+    pub fn received(&mut self, line: &str) {
+        let record = format!("{{\"type\":\"received\",\"at\":{},\"line\":{}}}", self.at(), json_string(line));
+        self.write(record);
+    }
+
+    /// A line sent back `took` milliseconds after the line it answers.
+    pub fn sent(&mut self, line: &str, took: u64) {
+        let record = format!("{{\"type\":\"sent\",\"at\":{},\"took\":{},\"line\":{}}}", self.at(), took, json_string(line));
+        self.write(record);
+    }
+
+    /// Every region on `map` at the start of `round`, if snapshots are on.
+    pub fn snapshot(&mut self, round: u64, map: &GameMap) {
+        if !self.snapshots {
+            return;
+        }
+        let regions: Vec<String> = map.region_ids().iter().map(|id| {
+            let region = map.region(*id).unwrap();
+            format!("[{},\"{}\",{}]", id, region.owner.as_str(), region.armies)
+        }).collect();
+        let record = format!("{{\"type\":\"map\",\"at\":{},\"round\":{},\"regions\":[{}]}}", self.at(), round, regions.join(","));
+        self.write(record);
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn at(&self) -> u64 {
+        let elapsed = self.started.elapsed();
+        elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64
+    }
+
+    /// Writes a record and flushes it. The first failure is logged and
+    /// stops the recording, losing the replay shouldn't lose the game.
+    fn write(&mut self, record: String) {
+        if self.failed {
+            return;
+        }
+        if let Err(e) = writeln!(self.out, "{}", record).and_then(|_| self.out.flush()) {
+            error!("replay stopped: {}", e);
+            self.failed = true;
+        }
+    }
+}
+
+/// Reads a line of a replay.
+pub fn read_record(line: &str) -> Result<Record, String> {
+    let mut chars = line.trim().chars().peekable();
+    let json = try!(parse_json(&mut chars));
+    if chars.next().is_some() {
+        return Err("trailing characters after the record".to_owned());
+    }
+    let kind = try!(field(&json, "type").and_then(text));
+    let at = try!(field(&json, "at").and_then(number));
+    match kind.as_ref() {
+        "start" => {
+            let version = try!(field(&json, "version").and_then(number));
+            if version > VERSION {
+                return Err(format!("replay is version {}, only {} and older can be read", version, VERSION));
+            }
+            Ok(Record::Start {
+                at: at,
+                version: version,
+                seed: try!(field(&json, "seed").and_then(number))
+            })
+        },
+        "received" => Ok(Record::Received {
+            at: at,
+            line: try!(field(&json, "line").and_then(text))
+        }),
+        "sent" => Ok(Record::Sent {
+            at: at,
+            took: try!(field(&json, "took").and_then(number)),
+            line: try!(field(&json, "line").and_then(text))
+        }),
+        "map" => {
+            let mut regions = Vec::new();
+            match try!(field(&json, "regions")) {
+                Json::Array(ref entries) => for entry in entries {
+                    match *entry {
+                        Json::Array(ref parts) if parts.len() == 3 => {
+                            let owner = match try!(text(&parts[1])).as_ref() {
+                                "ally" => OwnerValue::Ally,
+                                "enemy" => OwnerValue::Enemy,
+                                "neutral" => OwnerValue::Neutral,
+                                other => return Err(format!("unknown owner {}", other))
+                            };
+                            regions.push((try!(number(&parts[0])), owner, try!(number(&parts[2]))));
+                        },
+                        _ => return Err("regions have to be [id, owner, armies]".to_owned())
+                    }
+                },
+                _ => return Err("regions has to be a list".to_owned())
+            }
+            Ok(Record::Map {
+                at: at,
+                round: try!(field(&json, "round").and_then(number)),
+                regions: regions
+            })
+        },
+        other => Err(format!("unknown record type {}", other))
+    }
+}
+
+fn field<'a>(json: &'a Json, name: &str) -> Result<&'a Json, String> {
+    match *json {
+        Json::Object(ref fields) => fields.iter()
+            .find(|&&(ref key, _)| key == name)
+            .map(|&(_, ref value)| value)
+            .ok_or(format!("record has no {}", name)),
+        _ => Err("record isn't an object".to_owned())
+    }
+}
+
+fn text(json: &Json) -> Result<String, String> {
+    match *json {
+        Json::Text(ref text) => Ok(text.clone()),
+        _ => Err(format!("expected a string, got {:?}", json))
+    }
+}
+
+fn number(json: &Json) -> Result<u64, String> {
+    match *json {
+        Json::Number(ref digits) => digits.parse().map_err(|_| format!("expected a whole number, got {}", digits)),
+        _ => Err(format!("expected a whole number, got {:?}", json))
+    }
+}
+
+fn parse_json<I: Iterator<Item = char>>(chars: &mut ::std::iter::Peekable<I>) -> Result<Json, String> {
+    skip_whitespace(chars);
+    match chars.peek().cloned() {
+        Some('{') => {
+            chars.next();
+            let mut fields = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Json::Object(fields));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = match try!(parse_json(chars)) {
+                    Json::Text(key) => key,
+                    _ => return Err("object keys have to be strings".to_owned())
+                };
+                skip_whitespace(chars);
+                if chars.next() != Some(':') {
+                    return Err(format!("expected : after {}", key));
+                }
+                let value = try!(parse_json(chars));
+                fields.push((key, value));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {},
+                    Some('}') => return Ok(Json::Object(fields)),
+                    _ => return Err("expected , or } in an object".to_owned())
+                }
+            }
+        },
+        Some('[') => {
+            chars.next();
+            let mut values = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Json::Array(values));
+            }
+            loop {
+                values.push(try!(parse_json(chars)));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => {},
+                    Some(']') => return Ok(Json::Array(values)),
+                    _ => return Err("expected , or ] in a list".to_owned())
+                }
+            }
+        },
+        Some('"') => {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Json::Text(text)),
+                    Some('\\') => match chars.next() {
+                        Some('n') => text.push('\n'),
+                        Some('r') => text.push('\r'),
+                        Some('t') => text.push('\t'),
+                        Some('b') => text.push('\u{8}'),
+                        Some('f') => text.push('\u{c}'),
+                        Some('u') => {
+                            let hex: String = chars.by_ref().take(4).collect();
+                            let code = try!(u32::from_str_radix(&hex, 16).map_err(|_| format!("bad escape \\u{}", hex)));
+                            text.push(try!(::std::char::from_u32(code).ok_or(format!("bad escape \\u{}", hex))));
+                        },
+                        Some(c) => text.push(c),
+                        None => return Err("unfinished string".to_owned())
+                    },
+                    Some(c) => text.push(c),
+                    None => return Err("unfinished string".to_owned())
+                }
+            }
+        },
+        Some(c) if c == '-' || c.is_digit(10) => {
+            let mut number = String::new();
+            while let Some(c) = chars.peek().cloned() {
+                if c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E' || c.is_digit(10) {
+                    number.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            match number.parse::<f64>() {
+                Ok(_) => Ok(Json::Number(number)),
+                Err(_) => Err(format!("bad number {}", number))
+            }
+        },
+        Some(_) => {
+            // true, false and null, none of which the records use.
+            let mut word = String::new();
+            while let Some(c) = chars.peek().cloned() {
+                if !c.is_alphabetic() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            match word.as_ref() {
+                "true" | "false" | "null" => Ok(Json::Other),
+                _ => Err(format!("unexpected {}", word))
+            }
+        },
+        None => Err("unexpected end of the record".to_owned())
+    }
+}
+
+fn skip_whitespace<I: Iterator<Item = char>>(chars: &mut ::std::iter::Peekable<I>) {
+    while chars.peek().map_or(false, |c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// `text` as a JSON string, quotes included.
+pub fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c)
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[test]
+fn strings_are_escaped() {
+    assert_eq!(json_string("go place_armies 10000"), "\"go place_armies 10000\"");
+    assert_eq!(json_string("a \"b\"\\\n\u{1}"), "\"a \\\"b\\\"\\\\\\n\\u0001\"");
+}
+
+#[test]
+fn records_are_written_one_per_line() {
+    let mut map = GameMap::new();
+    map.add_super_region(1, 2);
+    map.add_region(1, 1);
+    map.add_region(2, 1);
+    map.update_map(1, OwnerValue::Ally, 4);
+
+    let mut replay = ReplayWriter::new(Vec::new(), false);
+    replay.start(7);
+    replay.received("go place_armies 10000");
+    replay.snapshot(1, &map);
+    replay.sent("player1 place_armies 1 5", 3);
+    replay.snapshots = true;
+    replay.snapshot(1, &map);
+    let text = String::from_utf8(replay.into_inner()).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("{\"type\":\"start\",\"at\":"));
+    assert!(lines[0].ends_with("\"version\":1,\"seed\":7}"));
+    assert!(lines[1].ends_with("\"line\":\"go place_armies 10000\"}"));
+    assert!(lines[2].contains("\"took\":3,"));
+    assert!(lines[3].ends_with("\"round\":1,\"regions\":[[1,\"ally\",4],[2,\"neutral\",2]]}"));
+}
+
+#[test]
+fn records_are_read_back() {
+    let mut map = GameMap::new();
+    map.add_super_region(1, 2);
+    map.add_region(1, 1);
+    map.update_map(1, OwnerValue::Enemy, 3);
+
+    let mut replay = ReplayWriter::new(Vec::new(), true);
+    // More than an f64 holds exactly.
+    replay.start(12345678901234567891);
+    replay.received("say \"hi\"\\");
+    replay.snapshot(2, &map);
+    let text = String::from_utf8(replay.into_inner()).unwrap();
+    let records: Vec<Record> = text.lines().map(|line| read_record(line).unwrap()).collect();
+    match records[0] {
+        Record::Start { version, seed, .. } => assert_eq!((version, seed), (VERSION, 12345678901234567891)),
+        _ => panic!("expected the start, got {:?}", records[0])
+    }
+    match records[1] {
+        Record::Received { ref line, .. } => assert_eq!(line, "say \"hi\"\\"),
+        _ => panic!("expected a received line, got {:?}", records[1])
+    }
+    match records[2] {
+        Record::Map { round, ref regions, .. } => {
+            assert_eq!(round, 2);
+            assert_eq!(regions, &vec![(1, OwnerValue::Enemy, 3)]);
+        },
+        _ => panic!("expected a map, got {:?}", records[2])
+    }
+    assert!(read_record("{\"type\":\"start\",\"at\":0,\"version\":99,\"seed\":1}").is_err());
+    assert!(read_record("{\"type\":\"sent\",\"at\":0}").is_err());
+    assert!(read_record("not json").is_err());
+}
@@ -0,0 +1,152 @@
+//! Playing out a round with full information.
+//!
+//! Mirrors what the engine does once both players have sent their moves:
+//! placements land first, then attack/transfer moves are executed one at a
+//! time, alternating between the players starting with a random one. Moves
+//! that became illegal along the way are skipped and moves asking for more
+//! armies than are left are cut down, armies that arrived in a region this
+//! round can't move on.
+
+use combat;
+use map::{GameMap, OwnerValue};
+use moves::Move;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Applies both players' moves to `map`, returning the moves that actually
+/// happened in the order they did.
+pub fn resolve_round<R: Rng>(map: &mut GameMap, ally: &[Move], enemy: &[Move], rng: &mut R) -> Vec<(OwnerValue, Move)> {
+    let mut executed = Vec::new();
+
+    for &(owner, moves) in [(OwnerValue::Ally, ally), (OwnerValue::Enemy, enemy)].iter() {
+        let mut left = map.income(owner);
+        for movement in moves {
+            if let Move::PlaceArmies(id, armies) = *movement {
+                let armies = armies.min(left);
+                let current = match map.region(id) {
+                    Some(region) if region.owner == owner => region.armies,
+                    _ => continue
+                };
+                if armies == 0 {
+                    continue;
+                }
+                left -= armies;
+                map.update_map(id, owner, current + armies);
+                executed.push((owner, Move::PlaceArmies(id, armies)));
+            }
+        }
+    }
+
+    let attacks = |moves: &[Move]| -> Vec<Move> {
+        moves.iter().filter(|movement| match **movement {
+            Move::AttackTransfer(_, _, _) => true,
+            _ => false
+        }).cloned().collect()
+    };
+    let ally_attacks = attacks(ally);
+    let enemy_attacks = attacks(enemy);
+    let mut order = if rng.gen() {
+        [(OwnerValue::Ally, ally_attacks.iter()), (OwnerValue::Enemy, enemy_attacks.iter())]
+    } else {
+        [(OwnerValue::Enemy, enemy_attacks.iter()), (OwnerValue::Ally, ally_attacks.iter())]
+    };
+
+    let mut arrived = HashMap::new();
+    loop {
+        let mut any = false;
+        for &mut (owner, ref mut moves) in order.iter_mut() {
+            if let Some(movement) = moves.next() {
+                any = true;
+                if let Some(done) = attack_transfer(map, owner, movement, &mut arrived, rng) {
+                    executed.push((owner, done));
+                }
+            }
+        }
+        if !any {
+            break;
+        }
+    }
+    executed
+}
+
+fn attack_transfer<R: Rng>(map: &mut GameMap, owner: OwnerValue, movement: &Move, arrived: &mut HashMap<u64, u64>, rng: &mut R) -> Option<Move> {
+    let (source_id, target_id, requested) = match *movement {
+        Move::AttackTransfer(source, target, armies) => (source, target, armies),
+        _ => return None
+    };
+    let (source_armies, target_owner, target_armies) = {
+        let source = match map.region(source_id) {
+            Some(region) if region.owner == owner && region.neighbor_ids.contains(&target_id) => region,
+            _ => return None
+        };
+        let target = map.region(target_id).unwrap();
+        (source.armies, target.owner, target.armies)
+    };
+    let stuck = 1 + arrived.get(&source_id).cloned().unwrap_or(0);
+    let armies = requested.min(source_armies.saturating_sub(stuck));
+    if armies == 0 {
+        return None;
+    }
+
+    if target_owner == owner {
+        map.update_map(source_id, owner, source_armies - armies);
+        map.update_map(target_id, owner, target_armies + armies);
+        *arrived.entry(target_id).or_insert(0) += armies;
+    } else {
+        let outcome = combat::simulate(armies, target_armies, rng);
+        if outcome.captured(armies, target_armies) {
+            let survivors = armies - outcome.attackers_lost;
+            map.update_map(source_id, owner, source_armies - armies);
+            map.update_map(target_id, owner, survivors);
+            arrived.insert(target_id, survivors);
+        } else {
+            map.update_map(source_id, owner, source_armies - outcome.attackers_lost);
+            map.update_map(target_id, target_owner, target_armies - outcome.defenders_lost);
+        }
+    }
+    Some(Move::AttackTransfer(source_id, target_id, armies))
+}
+
+#[cfg(test)]
+fn duel_map() -> GameMap {
+    // 1 - 2 - 3, we hold 1, the enemy holds 3 and 2 is neutral.
+    let mut map = GameMap::new();
+    map.add_super_region(1, 3);
+    for id in 1..4 {
+        map.add_region(id, 1);
+    }
+    map.add_region_neighbors(1, vec![2]);
+    map.add_region_neighbors(2, vec![3]);
+    map.update_map(1, OwnerValue::Ally, 2);
+    map.update_map(3, OwnerValue::Enemy, 2);
+    map
+}
+
+#[test]
+fn placements_are_capped_by_income() {
+    let mut map = duel_map();
+    let mut rng = ::rand::XorShiftRng::new_unseeded();
+    let done = resolve_round(&mut map, &[Move::PlaceArmies(1, 4), Move::PlaceArmies(1, 4)], &[Move::PlaceArmies(1, 5)], &mut rng);
+    assert_eq!(done, vec![(OwnerValue::Ally, Move::PlaceArmies(1, 4)), (OwnerValue::Ally, Move::PlaceArmies(1, 1))]);
+    assert_eq!(map.region(1).unwrap().armies, 7);
+}
+
+#[test]
+fn overwhelming_attack_captures() {
+    let mut map = duel_map();
+    let mut rng = ::rand::XorShiftRng::new_unseeded();
+    map.update_map(1, OwnerValue::Ally, 30);
+    resolve_round(&mut map, &[Move::AttackTransfer(1, 2, 29)], &[], &mut rng);
+    assert_eq!(map.region(2).unwrap().owner, OwnerValue::Ally);
+    assert_eq!(map.region(1).unwrap().armies, 1);
+}
+
+#[test]
+fn arrived_armies_stay_put() {
+    let mut map = duel_map();
+    let mut rng = ::rand::XorShiftRng::new_unseeded();
+    map.update_map(2, OwnerValue::Ally, 1);
+    let done = resolve_round(&mut map, &[Move::AttackTransfer(1, 2, 1), Move::AttackTransfer(2, 3, 1)], &[], &mut rng);
+    assert_eq!(done, vec![(OwnerValue::Ally, Move::AttackTransfer(1, 2, 1))]);
+    assert_eq!(map.region(2).unwrap().armies, 2);
+}
@@ -0,0 +1,171 @@
+//! Everything the bot knows about the game in progress.
+
+use map::GameMap;
+use parser::{SettingsValue, SetupMapValue};
+use picks::PickHistory;
+
+/// Rounds from the start that still count as the opening land grab.
+const EARLY_EXPANSION_ROUNDS: u64 = 8;
+/// Rounds before `max_rounds` where holding the most regions is what wins.
+const ENDGAME_ROUNDS: u64 = 10;
+
+/// The settings sent by the engine before the game starts, `starting_armies`
+/// is resent every round and doubles as our income for that round.
+// Using 64 bits because I assume the compiler/platform we are targetting is
+// also 64 bit.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub timebank: u64,
+    pub time_per_move: u64,
+    pub max_rounds: u64,
+    pub name: String,
+    pub opponent: String,
+    pub starting_regions: Vec<u64>,
+    pub starting_pick_amount: u64,
+    pub starting_armies: u64
+}
+
+#[derive(Debug)]
+pub struct GameState {
+    pub settings: Settings,
+    pub map: GameMap,
+    /// The engine never says which round it is, it is counted from the
+    /// `go place_armies` messages instead. 0 means we are still picking.
+    pub round: u64,
+    pub picks: PickHistory
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Picking,
+    EarlyExpansion,
+    MidGame,
+    /// Close to `max_rounds`, the game is decided on region count if nobody
+    /// is wiped out.
+    Endgame
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings{
+            timebank: 0,
+            time_per_move: 0,
+            max_rounds: 0,
+            name: "default".to_owned(),
+            opponent: "default".to_owned(),
+            starting_regions: Vec::new(),
+            starting_pick_amount: 0,
+            starting_armies: 0
+        }
+    }
+}
+
+impl GameState {
+    pub fn new() -> GameState {
+        GameState {
+            settings: Settings::new(),
+            map: GameMap::new(),
+            round: 0,
+            picks: PickHistory::new()
+        }
+    }
+
+    /// Applies one of the `setup_map` messages.
+    pub fn setup_map(&mut self, message: SetupMapValue) {
+        match message {
+            SetupMapValue::SuperRegions(super_regions) => {
+                for (id, value) in super_regions {
+                    self.map.add_super_region(id, value);
+                }
+            },
+            SetupMapValue::Regions(regions) => {
+                for (id, super_region) in regions {
+                    self.map.add_region(id, super_region);
+                }
+            },
+            SetupMapValue::Neighbors(new_neighbors) => {
+                for (id, neighbors) in new_neighbors {
+                    self.map.add_region_neighbors(id, neighbors);
+                }
+            },
+            SetupMapValue::Wastelands(wastelands) => {
+                for id in wastelands {
+                    self.map.upgrade_to_wasteland(id);
+                }
+            },
+            SetupMapValue::OpponentStartingRegions(enemies) => {
+                for id in enemies {
+                    self.map.mark_as_enemy(id);
+                }
+            }
+        }
+    }
+
+    pub fn apply_setting(&mut self, message: SettingsValue) {
+        match message {
+            SettingsValue::Timebank(time) => self.settings.timebank = time,
+            SettingsValue::TimePerMove(time) => self.settings.time_per_move = time,
+            SettingsValue::MaxRounds(rounds) => self.settings.max_rounds = rounds,
+            SettingsValue::YourBot(name) => self.settings.name = name,
+            SettingsValue::OpponentBot(name) => self.settings.opponent = name,
+            SettingsValue::StartingRegions(region_ids) => self.settings.starting_regions = region_ids,
+            SettingsValue::StartingPickAmount(value) => self.settings.starting_pick_amount = value,
+            SettingsValue::StartingArmies(value) => self.settings.starting_armies = value
+        };
+    }
+
+    /// Called for each `go place_armies`, which the engine sends exactly once
+    /// per round.
+    pub fn start_round(&mut self) {
+        self.round += 1;
+    }
+
+    /// Rounds left to play after the current one, `None` if the engine hasn't
+    /// told us `max_rounds`.
+    pub fn rounds_remaining(&self) -> Option<u64> {
+        if self.settings.max_rounds == 0 {
+            None
+        } else {
+            Some(self.settings.max_rounds.saturating_sub(self.round))
+        }
+    }
+
+    pub fn phase(&self) -> Phase {
+        if self.round == 0 {
+            return Phase::Picking;
+        }
+        match self.rounds_remaining() {
+            Some(remaining) if remaining < ENDGAME_ROUNDS => Phase::Endgame,
+            _ => if self.round <= EARLY_EXPANSION_ROUNDS {
+                Phase::EarlyExpansion
+            } else {
+                Phase::MidGame
+            }
+        }
+    }
+}
+
+#[test]
+fn phases_follow_the_round() {
+    let mut state = GameState::new();
+    state.settings.max_rounds = 60;
+    assert_eq!(state.phase(), Phase::Picking);
+    state.start_round();
+    assert_eq!(state.round, 1);
+    assert_eq!(state.rounds_remaining(), Some(59));
+    assert_eq!(state.phase(), Phase::EarlyExpansion);
+    state.round = 9;
+    assert_eq!(state.phase(), Phase::MidGame);
+    state.round = 51;
+    assert_eq!(state.phase(), Phase::Endgame);
+    state.round = 60;
+    assert_eq!(state.rounds_remaining(), Some(0));
+}
+
+#[test]
+fn unknown_max_rounds_never_ends() {
+    let mut state = GameState::new();
+    state.round = 500;
+    assert_eq!(state.rounds_remaining(), None);
+    assert_eq!(state.phase(), Phase::MidGame);
+}
@@ -0,0 +1,103 @@
+//! A greedy expander: takes whatever it can, as cheaply as it can.
+//!
+//! The whole income goes next to the cheapest region we don't hold,
+//! neutrals before the enemy's. Then every region attacks the neighbours it
+//! can take, cheapest first, for as long as its armies last. It never
+//! transfers, armies that can't take anything stay where they are.
+
+use combat::armies_needed;
+use map::{GameMap, OwnerValue, Region};
+use moves::Move;
+use rand::XorShiftRng;
+use state::GameState;
+use strategy::Strategy;
+
+pub struct GreedyStrategy;
+
+impl GreedyStrategy {
+    pub fn new() -> GreedyStrategy {
+        GreedyStrategy
+    }
+}
+
+impl Strategy for GreedyStrategy {
+    fn place_armies(&mut self, state: &GameState, _: u64, _: &mut XorShiftRng) -> Vec<Move> {
+        let map = &state.map;
+        let mut best: Option<((u64, bool, u64), &Region)> = None;
+        for region in map.allies() {
+            for target in targets(map, region) {
+                let key = (armies_needed(target.armies), target.owner == OwnerValue::Enemy, target.id);
+                let better = match best {
+                    Some((ref best_key, ref source)) => key < *best_key || (key == *best_key && region.armies > source.armies),
+                    None => true
+                };
+                if better {
+                    best = Some((key, region));
+                }
+            }
+        }
+        let source = match best {
+            Some((_, region)) => region.id,
+            None => match map.allies().first() {
+                Some(region) => region.id,
+                None => return Vec::new()
+            }
+        };
+        vec![Move::PlaceArmies(source, state.settings.starting_armies)]
+    }
+
+    fn attack_transfer(&mut self, state: &GameState, _: u64, _: &mut XorShiftRng) -> Vec<Move> {
+        let map = &state.map;
+        let mut moves = Vec::new();
+        for region in map.allies() {
+            let mut left = map.available_armies(region.id);
+            let mut targets = targets(map, region);
+            targets.sort_by_key(|target| (armies_needed(target.armies), target.id));
+            for target in targets {
+                let needed = armies_needed(target.armies);
+                if needed <= left {
+                    moves.push(Move::AttackTransfer(region.id, target.id, needed));
+                    left -= needed;
+                }
+            }
+        }
+        moves
+    }
+}
+
+/// The neighbours of `region` we don't hold.
+fn targets<'a>(map: &'a GameMap, region: &Region) -> Vec<&'a Region> {
+    region.neighbor_ids.iter()
+        .map(|id| map.region(*id).unwrap())
+        .filter(|neighbor| neighbor.owner != OwnerValue::Ally)
+        .collect()
+}
+
+#[test]
+fn cheapest_neighbours_go_first() {
+    // 1 - 2 - 3, we hold 2 with 3, 1 is neutral with 3 and the enemy holds
+    // 3 with 1.
+    let mut state = GameState::new();
+    state.settings.starting_armies = 5;
+    state.map.add_super_region(1, 2);
+    for id in 1..4 {
+        state.map.add_region(id, 1);
+    }
+    state.map.add_region_neighbors(2, vec![1, 3]);
+    state.map.update_map(1, OwnerValue::Neutral, 3);
+    state.map.update_map(2, OwnerValue::Ally, 3);
+    state.map.update_map(3, OwnerValue::Enemy, 1);
+
+    let mut strategy = GreedyStrategy::new();
+    let mut rng = ::strategy::seeded_rng(0);
+    let placements = strategy.place_armies(&state, 10000, &mut rng);
+    assert_eq!(placements, vec![Move::PlaceArmies(2, 5)]);
+    for movement in placements {
+        state.map.record_move(movement);
+    }
+    // 7 to spare, 2 take 3 and 5 take 1.
+    assert_eq!(strategy.attack_transfer(&state, 10000, &mut rng), vec![
+        Move::AttackTransfer(2, 3, 2),
+        Move::AttackTransfer(2, 1, 5)
+    ]);
+}
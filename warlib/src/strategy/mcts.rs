@@ -0,0 +1,327 @@
+//! Monte Carlo Tree Search over simultaneous turns.
+//!
+//! Both players move at the same time so the tree is searched with decoupled
+//! UCT: every node keeps separate statistics for our candidate turns and the
+//! opponent's, each side picks its own turn with UCB1 as if the other's
+//! choice was unknown, and the pair of turns leads to the child node. Rounds
+//! are played out with `rules::resolve_round()`, treating our map as the
+//! truth, and positions at the search horizon are scored from our side with
+//! `eval::evaluate()`.
+//!
+//! The whole turn is planned when the engine asks for placements, the
+//! attacks are kept until it asks for those.
+//!
+//! By default the search runs until its share of the time for the move is
+//! used up, so how far it gets, and the turn it picks, changes from run to
+//! run even with the same seed. Set `iterations`, `warbot --iterations <n>`
+//! on the command line, for games that have to play out the same again.
+
+use eval::{EvalWeights, evaluate};
+use map::{GameMap, OwnerValue};
+use movegen::{self, MoveGenConfig};
+use moves::Move;
+use rand::{Rng, XorShiftRng};
+use rules::resolve_round;
+use state::GameState;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use strategy::Strategy;
+
+#[derive(Debug, Clone)]
+pub struct MctsConfig {
+    /// UCB1 exploration constant, rewards are between 0 and 1.
+    pub exploration: f64,
+    /// Rounds searched ahead.
+    pub depth: u64,
+    /// Candidate turns considered for each player at every node.
+    pub candidates: usize,
+    /// How the candidate turns are generated.
+    pub movegen: MoveGenConfig,
+    /// How positions at the search horizon are scored.
+    pub eval: EvalWeights,
+    /// Run exactly this many iterations whatever the clock says. Searches are
+    /// only repeatable when this is set.
+    pub iterations: Option<u64>,
+    /// Share of the time we have for a move that the search may use.
+    pub time_fraction: f64
+}
+
+pub struct MctsStrategy {
+    config: MctsConfig,
+    planned: Vec<Move>
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Stat {
+    visits: u64,
+    reward: f64
+}
+
+struct Node {
+    visits: u64,
+    ally: Vec<Vec<Move>>,
+    enemy: Vec<Vec<Move>>,
+    ally_stats: Vec<Stat>,
+    enemy_stats: Vec<Stat>,
+    children: HashMap<(usize, usize), Node>
+}
+
+impl Default for MctsConfig {
+    fn default() -> MctsConfig {
+        MctsConfig {
+            exploration: 0.7,
+            depth: 2,
+            candidates: 6,
+            movegen: MoveGenConfig::default(),
+            eval: EvalWeights::default(),
+            iterations: None,
+            time_fraction: 0.8
+        }
+    }
+}
+
+impl MctsStrategy {
+    pub fn new(config: MctsConfig) -> MctsStrategy {
+        MctsStrategy {
+            config: config,
+            planned: Vec::new()
+        }
+    }
+
+    /// Searches from the current position and returns our best whole turn.
+    pub fn search(&mut self, state: &GameState, time: u64, rng: &mut XorShiftRng) -> Vec<Move> {
+        let budget = self.budget(state, time);
+        let started = Instant::now();
+        let mut root = Node::new();
+        let mut iterations = 0;
+
+        loop {
+            match self.config.iterations {
+                Some(limit) => if iterations >= limit {
+                    break;
+                },
+                None => if iterations > 0 && started.elapsed() >= budget {
+                    break;
+                }
+            }
+            let mut map = state.map.clone();
+            iterate(&mut root, &mut map, self.config.depth, &self.config, rng);
+            iterations += 1;
+        }
+
+        let elapsed = started.elapsed();
+        info!(
+            "mcts: {} iterations in {}ms (budget {}ms)",
+            iterations,
+            millis(elapsed),
+            millis(budget)
+        );
+        if root.ally.is_empty() {
+            return Vec::new();
+        }
+        for (index, stat) in root.ally_stats.iter().enumerate() {
+            debug!("mcts: candidate {} visits {} mean {:.3} {:?}", index, stat.visits, stat.mean(), root.ally[index]);
+        }
+        let best = most_visited(&root.ally_stats);
+        info!(
+            "mcts: chose candidate {} of {}, visits {} mean {:.3}",
+            best,
+            root.ally.len(),
+            root.ally_stats[best].visits,
+            root.ally_stats[best].mean()
+        );
+        info!("mcts: line {}", principal_line(&root));
+        root.ally[best].clone()
+    }
+
+    fn budget(&self, state: &GameState, time: u64) -> Duration {
+        let per_move = if state.settings.time_per_move > 0 {
+            state.settings.time_per_move.min(time)
+        } else {
+            time.min(500)
+        };
+        Duration::from_millis((per_move as f64 * self.config.time_fraction) as u64)
+    }
+}
+
+impl Strategy for MctsStrategy {
+    fn place_armies(&mut self, state: &GameState, time: u64, rng: &mut XorShiftRng) -> Vec<Move> {
+        let turn = self.search(state, time, rng);
+        let (placements, attacks): (Vec<Move>, Vec<Move>) = turn.into_iter().partition(|movement| match *movement {
+            Move::PlaceArmies(_, _) => true,
+            _ => false
+        });
+        self.planned = attacks;
+        placements
+    }
+
+    fn attack_transfer(&mut self, _: &GameState, _: u64, _: &mut XorShiftRng) -> Vec<Move> {
+        self.planned.drain(..).collect()
+    }
+}
+
+impl Stat {
+    fn mean(&self) -> f64 {
+        if self.visits == 0 {0.0} else {self.reward / self.visits as f64}
+    }
+}
+
+impl Node {
+    fn new() -> Node {
+        Node {
+            visits: 0,
+            ally: Vec::new(),
+            enemy: Vec::new(),
+            ally_stats: Vec::new(),
+            enemy_stats: Vec::new(),
+            children: HashMap::new()
+        }
+    }
+
+    fn expand(&mut self, map: &GameMap, config: &MctsConfig) {
+        self.ally = candidate_turns(map, OwnerValue::Ally, config);
+        self.enemy = candidate_turns(map, OwnerValue::Enemy, config);
+        self.ally_stats = vec![Stat::default(); self.ally.len()];
+        self.enemy_stats = vec![Stat::default(); self.enemy.len()];
+    }
+}
+
+/// One selection, expansion and backup pass. Returns the reward from our
+/// side.
+fn iterate<R: Rng>(node: &mut Node, map: &mut GameMap, depth: u64, config: &MctsConfig, rng: &mut R) -> f64 {
+    if depth == 0 || is_over(map) {
+        node.visits += 1;
+        return evaluate(map, &config.eval);
+    }
+    if node.ally.is_empty() {
+        node.expand(map, config);
+    }
+
+    let ally = select(&node.ally_stats, node.visits, config.exploration, false);
+    let enemy = select(&node.enemy_stats, node.visits, config.exploration, true);
+    resolve_round(map, &node.ally[ally], &node.enemy[enemy], rng);
+
+    let reward = {
+        let child = node.children.entry((ally, enemy)).or_insert_with(Node::new);
+        if child.visits == 0 {
+            child.visits += 1;
+            evaluate(map, &config.eval)
+        } else {
+            iterate(child, map, depth - 1, config, rng)
+        }
+    };
+
+    node.visits += 1;
+    node.ally_stats[ally].visits += 1;
+    node.ally_stats[ally].reward += reward;
+    node.enemy_stats[enemy].visits += 1;
+    node.enemy_stats[enemy].reward += reward;
+    reward
+}
+
+/// UCB1 over one player's candidates, the opponent wants our reward low.
+fn select(stats: &[Stat], visits: u64, exploration: f64, minimize: bool) -> usize {
+    let mut best = 0;
+    let mut best_value = ::std::f64::MIN;
+    for (index, stat) in stats.iter().enumerate() {
+        if stat.visits == 0 {
+            return index;
+        }
+        let mean = if minimize {1.0 - stat.mean()} else {stat.mean()};
+        let value = mean + exploration * ((visits.max(1) as f64).ln() / stat.visits as f64).sqrt();
+        if value > best_value {
+            best = index;
+            best_value = value;
+        }
+    }
+    best
+}
+
+fn most_visited(stats: &[Stat]) -> usize {
+    let mut best = 0;
+    for (index, stat) in stats.iter().enumerate() {
+        if stat.visits > stats[best].visits {
+            best = index;
+        }
+    }
+    best
+}
+
+/// The most visited pair of turns at each level, for the log.
+fn principal_line(root: &Node) -> String {
+    let mut parts = Vec::new();
+    let mut node = root;
+    while !node.ally.is_empty() {
+        let ally = most_visited(&node.ally_stats);
+        let enemy = most_visited(&node.enemy_stats);
+        let stat = node.ally_stats[ally];
+        parts.push(format!("(us {} them {}, {} visits, mean {:.3})", ally, enemy, stat.visits, stat.mean()));
+        match node.children.get(&(ally, enemy)) {
+            Some(child) => node = child,
+            None => break
+        }
+    }
+    parts.join(" -> ")
+}
+
+fn is_over(map: &GameMap) -> bool {
+    map.owned_by(OwnerValue::Ally).is_empty() || map.owned_by(OwnerValue::Enemy).is_empty()
+}
+
+fn candidate_turns(map: &GameMap, owner: OwnerValue, config: &MctsConfig) -> Vec<Vec<Move>> {
+    let mut turns = movegen::turns(map, owner, &config.movegen);
+    turns.truncate(config.candidates.max(1));
+    turns
+}
+
+fn millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + (duration.subsec_nanos() / 1_000_000) as u64
+}
+
+#[cfg(test)]
+fn test_state() -> GameState {
+    // A ring of six regions in two super regions, we hold 1 and the enemy 4.
+    let mut state = GameState::new();
+    state.settings.starting_armies = 5;
+    state.settings.time_per_move = 500;
+    state.map.add_super_region(1, 2);
+    state.map.add_super_region(2, 2);
+    for id in 1..7 {
+        state.map.add_region(id, if id <= 3 {1} else {2});
+    }
+    for id in 1..6 {
+        state.map.add_region_neighbors(id, vec![id + 1]);
+    }
+    state.map.add_region_neighbors(6, vec![1]);
+    state.map.update_map(1, OwnerValue::Ally, 5);
+    state.map.update_map(4, OwnerValue::Enemy, 5);
+    state
+}
+
+#[test]
+fn fixed_seed_is_repeatable() {
+    let state = test_state();
+    let mut config = MctsConfig::default();
+    config.iterations = Some(200);
+    let first = MctsStrategy::new(config.clone()).search(&state, 10000, &mut ::strategy::seeded_rng(42));
+    let second = MctsStrategy::new(config).search(&state, 10000, &mut ::strategy::seeded_rng(42));
+    assert!(!first.is_empty());
+    assert_eq!(first, second);
+}
+
+#[test]
+fn plans_placements_then_attacks() {
+    let state = test_state();
+    let mut config = MctsConfig::default();
+    config.iterations = Some(50);
+    let mut strategy = MctsStrategy::new(config);
+    let mut rng = ::strategy::seeded_rng(0);
+    let placements = strategy.place_armies(&state, 10000, &mut rng);
+    assert_eq!(placements.iter().map(|movement| movement.armies()).sum::<u64>(), 5);
+    for movement in strategy.attack_transfer(&state, 10000, &mut rng) {
+        match movement {
+            Move::AttackTransfer(source, _, _) => assert_eq!(source, 1),
+            _ => panic!("got a placement in the attack phase")
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! How the bot decides what to do.
+//!
+//! The `Bot` takes care of the protocol and keeps the `GameState` up to date,
+//! a `Strategy` is asked for moves whenever the engine wants some. Whatever
+//! it returns is checked and repaired by the bot before being sent, so a
+//! strategy doesn't have to be careful about legality.
+//!
+//! Besides the strategies meant to win there are simple reference opponents
+//! to test them against: `random`, `greedy`, `turtle` and `rusher`.
+//!
+//! Strategies get their randomness from the generator the bot passes in,
+//! which is seeded once per game so a game can be replayed exactly.
+
+pub mod greedy;
+pub mod mcts;
+pub mod planner;
+pub mod random;
+pub mod rusher;
+pub mod turtle;
+
+pub use self::greedy::GreedyStrategy;
+pub use self::mcts::{MctsConfig, MctsStrategy};
+pub use self::planner::{PlannerConfig, PlannerStrategy};
+pub use self::random::RandomStrategy;
+pub use self::rusher::RusherStrategy;
+pub use self::turtle::TurtleStrategy;
+
+use eval::EvalWeights;
+use moves::Move;
+use ordering::OrderingPolicy;
+use picks::{PickOptions, choose_pick};
+use rand::{SeedableRng, XorShiftRng};
+use state::GameState;
+
+pub trait Strategy {
+    /// One of `offered` to start in. By default the best pick according to
+    /// `picks::choose_pick()`.
+    fn pick_starting_region(&mut self, state: &GameState, offered: &[u64], _: &mut XorShiftRng) -> Option<u64> {
+        choose_pick(state, &state.picks, offered, &PickOptions::default())
+    }
+
+    /// Placements for this round, `time` is what is left in our timebank in
+    /// milliseconds.
+    fn place_armies(&mut self, state: &GameState, time: u64, rng: &mut XorShiftRng) -> Vec<Move>;
+
+    /// Attacks and transfers for this round. The placements returned before
+    /// are already recorded on `state.map`.
+    fn attack_transfer(&mut self, state: &GameState, time: u64, rng: &mut XorShiftRng) -> Vec<Move>;
+
+    /// The order our moves are sent in, see `ordering`. By default
+    /// reinforcements first and defensive transfers last.
+    fn ordering(&self) -> OrderingPolicy {
+        OrderingPolicy::staged()
+    }
+}
+
+/// What `by_name()` changes from the strategies' defaults. Strategies that
+/// don't have a use for an option ignore it.
+#[derive(Debug, Clone, Default)]
+pub struct StrategyOptions {
+    /// How strategies that score positions score them.
+    pub weights: EvalWeights,
+    /// Iterations for strategies that search, until the time for the move is
+    /// up when `None`.
+    pub iterations: Option<u64>,
+    /// Have the planner plan all of its starting picks ahead, see
+    /// `PickOptions::plan_ahead`.
+    pub plan_picks: bool
+}
+
+/// Builds one of the strategies by the name used on the command line.
+pub fn by_name(name: &str, options: &StrategyOptions) -> Option<Box<Strategy + Send>> {
+    match name {
+        "random" => Some(Box::new(RandomStrategy::new())),
+        "greedy" => Some(Box::new(GreedyStrategy::new())),
+        "turtle" => Some(Box::new(TurtleStrategy::new())),
+        "rusher" => Some(Box::new(RusherStrategy::new())),
+        "planner" => {
+            let mut config = PlannerConfig::default();
+            config.picks.plan_ahead = options.plan_picks;
+            Some(Box::new(PlannerStrategy::new(config)))
+        },
+        "mcts" => {
+            let mut config = MctsConfig::default();
+            config.eval = options.weights.clone();
+            config.iterations = options.iterations;
+            Some(Box::new(MctsStrategy::new(config)))
+        },
+        _ => None
+    }
+}
+
+/// A fast generator that plays the same given the same seed.
+pub fn seeded_rng(seed: u64) -> XorShiftRng {
+    // XorShift can't be seeded with all zeros, the constants keep it away
+    // from that whatever seed we are given.
+    XorShiftRng::from_seed([
+        seed as u32 ^ 0x9e3779b9,
+        (seed >> 32) as u32 ^ 0x7f4a7c15,
+        0x6a09e667,
+        0xbb67ae85
+    ])
+}
@@ -0,0 +1,176 @@
+//! Goal-directed play: pick the super regions worth taking with
+//! `conquest::plan()` and work towards them every round.
+//!
+//! Threatened regions are held first when holding them saves more per army
+//! than the best conquest plan earns, the rest of the income goes to the
+//! plans and the armies kept home for defence don't join the attacks. Idle
+//! interior armies are walked towards the threatened regions and the ones
+//! attacking with `logistics::route()`.
+
+use conquest::{self, ConquestOptions};
+use defence::{self, DefenceOptions};
+use logistics;
+use moves::Move;
+use picks::{PickOptions, choose_pick};
+use rand::XorShiftRng;
+use state::GameState;
+use strategy::Strategy;
+
+#[derive(Debug, Clone, Default)]
+pub struct PlannerConfig {
+    pub conquest: ConquestOptions,
+    pub defence: DefenceOptions,
+    pub picks: PickOptions
+}
+
+pub struct PlannerStrategy {
+    config: PlannerConfig,
+    planned: Vec<Move>
+}
+
+impl PlannerStrategy {
+    pub fn new(config: PlannerConfig) -> PlannerStrategy {
+        PlannerStrategy {
+            config: config,
+            planned: Vec::new()
+        }
+    }
+}
+
+impl Strategy for PlannerStrategy {
+    fn pick_starting_region(&mut self, state: &GameState, offered: &[u64], _: &mut XorShiftRng) -> Option<u64> {
+        choose_pick(state, &state.picks, offered, &self.config.picks)
+    }
+
+    fn place_armies(&mut self, state: &GameState, _: u64, _: &mut XorShiftRng) -> Vec<Move> {
+        let income = state.settings.starting_armies;
+        let plans = conquest::plan(&state.map, &self.config.conquest);
+        let threats = defence::threats(&state.map, &self.config.defence);
+        let threshold = plans.first().map_or(0.0, |plan| plan.score);
+        let (mut turn, reserved) = defence::defend(&threats, income, threshold);
+        for threat in threats.iter().filter(|threat| reserved.contains_key(&threat.region_id)) {
+            info!(
+                "planner: holding {} against {} with {} more",
+                threat.region_id,
+                threat.attackers,
+                threat.shortfall
+            );
+        }
+
+        let mut map = state.map.clone();
+        for movement in turn.iter() {
+            map.record_move(movement.clone());
+        }
+        let spent = turn.iter().map(|movement| movement.armies()).sum::<u64>();
+        for plan in plans.iter() {
+            info!(
+                "planner: super region {} costs {} over {} rounds, targets {:?}",
+                plan.super_region_id,
+                plan.cost,
+                plan.rounds,
+                plan.targets
+            );
+        }
+        let orders = conquest::orders(&map, &plans, income - spent, &reserved);
+        let mut staging = Vec::new();
+        for movement in orders.iter() {
+            if let Move::AttackTransfer(source, _, _) = *movement {
+                staging.push(source);
+            }
+            map.record_move(movement.clone());
+        }
+        turn.extend(orders);
+
+        let threatened: Vec<(u64, f64)> = threats.iter().map(|threat| (threat.region_id, threat.at_stake)).collect();
+        let frontier = logistics::frontier_values(&map, &threatened, &staging);
+        turn.extend(logistics::route(&map, &frontier, &reserved));
+        let (placements, attacks): (Vec<Move>, Vec<Move>) = turn.into_iter().partition(|movement| match *movement {
+            Move::PlaceArmies(_, _) => true,
+            _ => false
+        });
+        self.planned = attacks;
+        placements
+    }
+
+    fn attack_transfer(&mut self, _: &GameState, _: u64, _: &mut XorShiftRng) -> Vec<Move> {
+        self.planned.drain(..).collect()
+    }
+}
+
+#[test]
+fn attacks_follow_the_placements() {
+    let mut state = GameState::new();
+    state.settings.starting_armies = 6;
+    state.map.add_super_region(1, 2);
+    for id in 1..4 {
+        state.map.add_region(id, 1);
+    }
+    state.map.add_region_neighbors(1, vec![2]);
+    state.map.add_region_neighbors(2, vec![3]);
+    state.map.update_map(2, ::map::OwnerValue::Ally, 1);
+
+    let mut strategy = PlannerStrategy::new(PlannerConfig::default());
+    let mut rng = ::strategy::seeded_rng(0);
+    let placements = strategy.place_armies(&state, 10000, &mut rng);
+    assert_eq!(placements.iter().map(|movement| movement.armies()).sum::<u64>(), 6);
+    for movement in placements {
+        state.map.record_move(movement);
+    }
+    let attacks = strategy.attack_transfer(&state, 10000, &mut rng);
+    assert_eq!(attacks, vec![Move::AttackTransfer(2, 1, 3), Move::AttackTransfer(2, 3, 3)]);
+    assert!(strategy.attack_transfer(&state, 10000, &mut rng).is_empty());
+}
+
+#[test]
+fn threatened_bonus_is_held_before_attacking() {
+    use map::OwnerValue;
+    // We hold super region 1, regions 1 and 2. The enemy sits on 3 next to
+    // 2 and neutral 4 next to 1 would start super region 2.
+    let mut state = GameState::new();
+    state.settings.starting_armies = 5;
+    state.map.add_super_region(1, 2);
+    state.map.add_super_region(2, 2);
+    for (id, super_region) in vec![(1, 1), (2, 1), (3, 2), (4, 2)] {
+        state.map.add_region(id, super_region);
+    }
+    state.map.add_region_neighbors(1, vec![2, 4]);
+    state.map.add_region_neighbors(2, vec![3]);
+    state.map.add_region_neighbors(3, vec![4]);
+    state.map.update_map(1, OwnerValue::Ally, 3);
+    state.map.update_map(2, OwnerValue::Ally, 4);
+    state.map.update_map(3, OwnerValue::Enemy, 3);
+
+    let mut strategy = PlannerStrategy::new(PlannerConfig::default());
+    let mut rng = ::strategy::seeded_rng(0);
+    let placements = strategy.place_armies(&state, 10000, &mut rng);
+    assert_eq!(placements[0], Move::PlaceArmies(2, 1));
+    for movement in placements {
+        state.map.record_move(movement);
+    }
+    // 2 keeps the 5 it needs against 2 attackers plus an income of 5.
+    assert_eq!(strategy.attack_transfer(&state, 10000, &mut rng), vec![Move::AttackTransfer(1, 4, 3)]);
+}
+
+#[test]
+fn interior_armies_join_the_front() {
+    use map::OwnerValue;
+    // 1 - 2 - 3, we hold 1 and 2 with 3 neutral.
+    let mut state = GameState::new();
+    state.settings.starting_armies = 5;
+    state.map.add_super_region(1, 2);
+    for id in 1..4 {
+        state.map.add_region(id, 1);
+    }
+    state.map.add_region_neighbors(1, vec![2]);
+    state.map.add_region_neighbors(2, vec![3]);
+    state.map.update_map(1, OwnerValue::Ally, 6);
+    state.map.update_map(2, OwnerValue::Ally, 1);
+
+    let mut strategy = PlannerStrategy::new(PlannerConfig::default());
+    let mut rng = ::strategy::seeded_rng(0);
+    strategy.place_armies(&state, 10000, &mut rng);
+    assert_eq!(strategy.attack_transfer(&state, 10000, &mut rng), vec![
+        Move::AttackTransfer(2, 3, 3),
+        Move::AttackTransfer(1, 2, 5)
+    ]);
+}
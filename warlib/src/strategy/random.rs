@@ -0,0 +1,41 @@
+//! The original bot: spread the income one army at a time over random
+//! regions and attack a random neighbour from anywhere with armies to spare.
+
+use moves::Move;
+use ordering::OrderingPolicy;
+use rand::{XorShiftRng, sample};
+use state::GameState;
+use strategy::Strategy;
+
+pub struct RandomStrategy;
+
+impl RandomStrategy {
+    pub fn new() -> RandomStrategy {
+        RandomStrategy
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn place_armies(&mut self, state: &GameState, _: u64, rng: &mut XorShiftRng) -> Vec<Move> {
+        let regions = state.map.allies();
+        let choices = sample(rng, regions.iter(), state.settings.starting_armies as usize);
+        choices.iter().map(|region| Move::PlaceArmies(region.id, 1)).collect()
+    }
+
+    fn attack_transfer(&mut self, state: &GameState, _: u64, rng: &mut XorShiftRng) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for region in state.map.allies() {
+            if state.map.available_armies(region.id) >= 3 {
+                let choices = sample(rng, region.neighbor_ids.iter(), 1);
+                if let Some(target) = choices.get(0) {
+                    moves.push(Move::AttackTransfer(region.id, **target, 3));
+                }
+            }
+        }
+        moves
+    }
+
+    fn ordering(&self) -> OrderingPolicy {
+        OrderingPolicy::as_issued()
+    }
+}
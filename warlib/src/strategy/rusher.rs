@@ -0,0 +1,102 @@
+//! An all-in rusher: piles everything into one stack and marches it at the
+//! enemy.
+//!
+//! The income goes to our region closest to the enemy, or to the nearest
+//! region we don't hold while no enemy is in sight. Every army that can move
+//! takes one step towards it, attacking whatever is in the way when it is
+//! strong enough to win and waiting to grow when it isn't.
+
+use combat::armies_needed;
+use map::OwnerValue;
+use moves::Move;
+use rand::XorShiftRng;
+use state::GameState;
+use std::collections::HashMap;
+use strategy::Strategy;
+
+pub struct RusherStrategy;
+
+impl RusherStrategy {
+    pub fn new() -> RusherStrategy {
+        RusherStrategy
+    }
+}
+
+impl Strategy for RusherStrategy {
+    fn place_armies(&mut self, state: &GameState, _: u64, _: &mut XorShiftRng) -> Vec<Move> {
+        let distances = distances_to_target(state);
+        let closest = state.map.allies().into_iter()
+            .min_by(|a, b| {
+                let distance = |id| distances.get(&id).cloned().unwrap_or(u64::max_value());
+                distance(a.id).cmp(&distance(b.id))
+                    .then(b.armies.cmp(&a.armies))
+                    .then(a.id.cmp(&b.id))
+            })
+            .map(|region| region.id);
+        match closest {
+            Some(id) => vec![Move::PlaceArmies(id, state.settings.starting_armies)],
+            None => Vec::new()
+        }
+    }
+
+    fn attack_transfer(&mut self, state: &GameState, _: u64, _: &mut XorShiftRng) -> Vec<Move> {
+        let map = &state.map;
+        let distances = distances_to_target(state);
+        let mut moves = Vec::new();
+        for region in map.allies() {
+            let armies = map.available_armies(region.id);
+            let next = region.neighbor_ids.iter()
+                .filter(|id| distances.contains_key(id))
+                .min_by_key(|id| (distances[*id], **id));
+            let next = match next {
+                Some(id) if armies > 0 => map.region(*id).unwrap(),
+                _ => continue
+            };
+            if next.owner == OwnerValue::Ally || armies >= armies_needed(next.armies) {
+                moves.push(Move::AttackTransfer(region.id, next.id, armies));
+            }
+        }
+        moves
+    }
+}
+
+/// Steps to the closest enemy region, or to the closest region we don't hold
+/// when there are none in sight.
+fn distances_to_target(state: &GameState) -> HashMap<u64, u64> {
+    let map = &state.map;
+    let mut targets: Vec<u64> = map.owned_by(OwnerValue::Enemy).iter().map(|region| region.id).collect();
+    if targets.is_empty() {
+        targets = map.owned_by(OwnerValue::Neutral).iter().map(|region| region.id).collect();
+    }
+    map.distances(&targets)
+}
+
+#[test]
+fn armies_march_at_the_enemy() {
+    // 1 - 2 - 3 - 4, we hold 1 and 2, 3 is neutral and the enemy holds 4.
+    let mut state = GameState::new();
+    state.settings.starting_armies = 5;
+    state.map.add_super_region(1, 2);
+    for id in 1..5 {
+        state.map.add_region(id, 1);
+    }
+    for id in 1..4 {
+        state.map.add_region_neighbors(id, vec![id + 1]);
+    }
+    state.map.update_map(1, OwnerValue::Ally, 6);
+    state.map.update_map(2, OwnerValue::Ally, 1);
+    state.map.update_map(3, OwnerValue::Neutral, 2);
+    state.map.update_map(4, OwnerValue::Enemy, 5);
+
+    let mut strategy = RusherStrategy::new();
+    let mut rng = ::strategy::seeded_rng(0);
+    let placements = strategy.place_armies(&state, 10000, &mut rng);
+    assert_eq!(placements, vec![Move::PlaceArmies(2, 5)]);
+    for movement in placements {
+        state.map.record_move(movement);
+    }
+    assert_eq!(strategy.attack_transfer(&state, 10000, &mut rng), vec![
+        Move::AttackTransfer(1, 2, 5),
+        Move::AttackTransfer(2, 3, 5)
+    ]);
+}
@@ -0,0 +1,120 @@
+//! A turtle: holds on to what it has and only grows where it is safe.
+//!
+//! The income goes to the border region facing the most enemy armies, or the
+//! weakest border region while no enemy is in sight. Regions next to the
+//! enemy keep their armies at home and the enemy is never attacked. Neutrals
+//! are only attacked with twice the armies it takes, and armies in the
+//! interior walk towards the border.
+
+use combat::armies_needed;
+use map::{GameMap, OwnerValue};
+use moves::Move;
+use rand::XorShiftRng;
+use state::GameState;
+use strategy::Strategy;
+
+pub struct TurtleStrategy;
+
+impl TurtleStrategy {
+    pub fn new() -> TurtleStrategy {
+        TurtleStrategy
+    }
+}
+
+impl Strategy for TurtleStrategy {
+    fn place_armies(&mut self, state: &GameState, _: u64, _: &mut XorShiftRng) -> Vec<Move> {
+        let map = &state.map;
+        let mut borders: Vec<(u64, u64, u64)> = map.allies().iter()
+            .filter(|region| map.is_border(OwnerValue::Ally, region.id))
+            .map(|region| (threat(map, region.id), region.armies, region.id))
+            .collect();
+        // Most threatened first, then the weakest.
+        borders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+        match borders.first() {
+            Some(&(_, _, id)) => vec![Move::PlaceArmies(id, state.settings.starting_armies)],
+            None => Vec::new()
+        }
+    }
+
+    fn attack_transfer(&mut self, state: &GameState, _: u64, _: &mut XorShiftRng) -> Vec<Move> {
+        let map = &state.map;
+        let borders: Vec<u64> = map.allies().iter()
+            .filter(|region| map.is_border(OwnerValue::Ally, region.id))
+            .map(|region| region.id)
+            .collect();
+        let distances = map.owned_distances(OwnerValue::Ally, &borders);
+
+        let mut moves = Vec::new();
+        for region in map.allies() {
+            let mut left = map.available_armies(region.id);
+            if left == 0 || threat(map, region.id) > 0 {
+                continue;
+            }
+            if !borders.contains(&region.id) {
+                let closer = region.neighbor_ids.iter()
+                    .filter(|id| distances.contains_key(id))
+                    .min_by_key(|id| (distances[*id], **id));
+                if let Some(next) = closer {
+                    moves.push(Move::AttackTransfer(region.id, *next, left));
+                }
+                continue;
+            }
+            let mut neutrals: Vec<(u64, u64)> = region.neighbor_ids.iter()
+                .map(|id| map.region(*id).unwrap())
+                .filter(|neighbor| neighbor.owner == OwnerValue::Neutral)
+                .map(|neighbor| (2 * armies_needed(neighbor.armies), neighbor.id))
+                .collect();
+            neutrals.sort();
+            for (armies, id) in neutrals {
+                if armies <= left {
+                    moves.push(Move::AttackTransfer(region.id, id, armies));
+                    left -= armies;
+                }
+            }
+        }
+        moves
+    }
+}
+
+/// Enemy armies next to `id`.
+fn threat(map: &GameMap, id: u64) -> u64 {
+    map.region(id).unwrap().neighbor_ids.iter()
+        .map(|neighbor| map.region(*neighbor).unwrap())
+        .filter(|neighbor| neighbor.owner == OwnerValue::Enemy)
+        .map(|neighbor| neighbor.armies)
+        .sum()
+}
+
+#[test]
+fn threatened_regions_are_fortified() {
+    // 1 - 2 - 3 - 4, we hold 1 to 3 and the enemy 4. 2 also borders neutral 5.
+    let mut state = GameState::new();
+    state.settings.starting_armies = 5;
+    state.map.add_super_region(1, 2);
+    for id in 1..6 {
+        state.map.add_region(id, 1);
+    }
+    for id in 1..4 {
+        state.map.add_region_neighbors(id, vec![id + 1]);
+    }
+    state.map.add_region_neighbors(2, vec![5]);
+    state.map.update_map(1, OwnerValue::Ally, 4);
+    state.map.update_map(2, OwnerValue::Ally, 9);
+    state.map.update_map(3, OwnerValue::Ally, 3);
+    state.map.update_map(4, OwnerValue::Enemy, 6);
+    state.map.update_map(5, OwnerValue::Neutral, 2);
+
+    let mut strategy = TurtleStrategy::new();
+    let mut rng = ::strategy::seeded_rng(0);
+    let placements = strategy.place_armies(&state, 10000, &mut rng);
+    assert_eq!(placements, vec![Move::PlaceArmies(3, 5)]);
+    for movement in placements {
+        state.map.record_move(movement);
+    }
+    // 3 stays put, 2 takes 5 with twice what it needs and 1 walks to 2.
+    let needed = 2 * armies_needed(2);
+    assert_eq!(strategy.attack_transfer(&state, 10000, &mut rng), vec![
+        Move::AttackTransfer(1, 2, 3),
+        Move::AttackTransfer(2, 5, needed)
+    ]);
+}
@@ -0,0 +1,324 @@
+//! Looking through a recorded game round by round.
+//!
+//! `timeline()` plays the lines one bot was sent (see `fodder::Recording`)
+//! into a map the way the bot does, keeping a `Frame` for every round: the
+//! map when the bot was asked for placements, which regions that round's
+//! update_map showed, the opponent moves the bot was shown and the moves it
+//! answered with. Regions the update didn't show are fogged, all the bot has
+//! for them is what it last saw.
+//!
+//! Frames render as text, regions grouped by super region:
+//!
+//! ```text
+//! super region 2, bonus 3
+//!     4  ally        6  *
+//!     5  enemy       3   !  fog
+//! ```
+//!
+//! `*` marks regions our moves touched and `!` the opponent's. `html()` puts
+//! every round on a single page to step through in a browser.
+
+use engine::parse_moves;
+use fodder::Recording;
+use map::{GameMap, OwnerValue};
+use moves::Move;
+use parser::{Message, OpponentMoveValue, parse};
+use state::GameState;
+
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub round: u64,
+    /// The map as the bot saw it when asked for placements.
+    pub map: GameMap,
+    /// Regions the round's update_map showed.
+    pub visible: Vec<u64>,
+    /// What we placed and sent this round.
+    pub ours: Vec<Move>,
+    /// The opponent's moves from last round that we were shown.
+    pub theirs: Vec<Move>
+}
+
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    pub name: String,
+    pub opponent: String,
+    pub frames: Vec<Frame>
+}
+
+impl Frame {
+    pub fn is_visible(&self, id: u64) -> bool {
+        self.visible.contains(&id)
+    }
+}
+
+/// Every round of `recording`, as the bot saw it.
+pub fn timeline(recording: &Recording) -> Timeline {
+    let mut state = GameState::new();
+    let mut visible = Vec::new();
+    let mut theirs = Vec::new();
+    let mut frames: Vec<Frame> = Vec::new();
+    for exchange in recording.exchanges.iter() {
+        let message = match parse(exchange.line.clone()) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("skipping '{}': {}", exchange.line, e);
+                continue;
+            }
+        };
+        match message {
+            Message::SetupMap(setup) => state.setup_map(setup),
+            Message::Settings(setting) => state.apply_setting(setting),
+            Message::UpdateMap(regions) => {
+                visible.clear();
+                for (id, name, armies) in regions {
+                    let owner = owner(&state, &name);
+                    state.map.update_map(id, owner, armies);
+                    visible.push(id);
+                }
+                state.map.update_fog(visible.clone());
+            },
+            Message::OpponentMoves(moves) => for movement in moves {
+                match movement {
+                    OpponentMoveValue::PlaceArmies(_, id, armies) => {
+                        state.map.mark_as_enemy(id);
+                        theirs.push(Move::PlaceArmies(id, armies));
+                    },
+                    OpponentMoveValue::AttackTransfer(_, source, target, armies) => {
+                        state.map.mark_as_enemy(source);
+                        theirs.push(Move::AttackTransfer(source, target, armies));
+                    }
+                }
+            },
+            Message::GoPlaceArmies(_) => {
+                state.start_round();
+                frames.push(Frame {
+                    round: state.round,
+                    map: state.map.clone(),
+                    visible: visible.clone(),
+                    ours: Vec::new(),
+                    theirs: theirs.clone()
+                });
+                theirs.clear();
+            },
+            Message::GoAttackTransfer(_) | Message::PickStartingRegion(..) => {}
+        }
+        if let (Some(frame), Some(answer)) = (frames.last_mut(), exchange.answer.as_ref()) {
+            if exchange.line.starts_with("go ") {
+                frame.ours.extend(parse_moves(answer, &state.settings.name));
+            }
+        }
+    }
+    Timeline {
+        name: state.settings.name.clone(),
+        opponent: state.settings.opponent.clone(),
+        frames: frames
+    }
+}
+
+fn owner(state: &GameState, name: &str) -> OwnerValue {
+    if name == state.settings.name {
+        OwnerValue::Ally
+    } else if name == state.settings.opponent {
+        OwnerValue::Enemy
+    } else {
+        OwnerValue::Neutral
+    }
+}
+
+fn touches(moves: &[Move], id: u64) -> bool {
+    moves.iter().any(|movement| match *movement {
+        Move::PlaceArmies(region, _) => region == id,
+        Move::AttackTransfer(source, target, _) => source == id || target == id
+    })
+}
+
+fn commands(moves: &[Move], name: &str) -> String {
+    if moves.is_empty() {
+        "none".to_owned()
+    } else {
+        moves.iter().map(|movement| movement.to_command(name)).collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// The frame as text, see the module docs.
+pub fn text(timeline: &Timeline, frame: &Frame) -> String {
+    let mut lines = Vec::new();
+    lines.push(format!("round {}", frame.round));
+    lines.push(format!("our moves:   {}", commands(&frame.ours, &timeline.name)));
+    lines.push(format!("their moves: {}", commands(&frame.theirs, &timeline.opponent)));
+    for super_id in frame.map.super_region_ids() {
+        let super_region = frame.map.super_region(super_id).unwrap();
+        lines.push(String::new());
+        let mut heading = format!("super region {}, bonus {}", super_id, super_region.value);
+        if frame.map.owns_super_region(OwnerValue::Ally, super_id) {
+            heading.push_str(", ours");
+        } else if frame.map.owns_super_region(OwnerValue::Enemy, super_id) {
+            heading.push_str(", theirs");
+        }
+        lines.push(heading);
+        let mut ids = super_region.region_ids.clone();
+        ids.sort();
+        for id in ids {
+            let region = frame.map.region(id).unwrap();
+            let mut line = format!(
+                "  {:>4}  {:<8} {:>4}  {}{}",
+                id,
+                region.owner.as_str(),
+                region.armies,
+                if touches(&frame.ours, id) {"*"} else {" "},
+                if touches(&frame.theirs, id) {"!"} else {" "}
+            );
+            if !frame.is_visible(id) {
+                line.push_str("  fog");
+            }
+            if region.wasteland {
+                line.push_str("  wasteland");
+            }
+            lines.push(line.trim_end().to_owned());
+        }
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Every frame on one page, with buttons and the arrow keys to step through
+/// them.
+pub fn html(timeline: &Timeline, title: &str) -> String {
+    let mut page = String::new();
+    page.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    page.push_str(&format!("<title>{}</title>\n", escape(title)));
+    page.push_str(STYLE);
+    page.push_str("</head>\n<body>\n");
+    page.push_str(&format!("<h1>{}</h1>\n", escape(title)));
+    page.push_str("<p><button id=\"previous\">previous</button> <button id=\"next\">next</button> \
+        <span class=\"legend\"><span class=\"ours\">our move</span> <span class=\"theirs\">their move</span> \
+        <span class=\"fog\">fogged</span></span></p>\n");
+    for frame in timeline.frames.iter() {
+        page.push_str(&format!("<section class=\"round\" id=\"round-{}\">\n<h2>round {}</h2>\n", frame.round, frame.round));
+        page.push_str(&format!("<p>our moves: {}</p>\n", escape(&commands(&frame.ours, &timeline.name))));
+        page.push_str(&format!("<p>their moves: {}</p>\n", escape(&commands(&frame.theirs, &timeline.opponent))));
+        for super_id in frame.map.super_region_ids() {
+            let super_region = frame.map.super_region(super_id).unwrap();
+            page.push_str(&format!("<div class=\"super\"><h3>super region {}, bonus {}</h3>\n", super_id, super_region.value));
+            let mut ids = super_region.region_ids.clone();
+            ids.sort();
+            for id in ids {
+                let region = frame.map.region(id).unwrap();
+                let mut classes = vec![region.owner.as_str()];
+                if touches(&frame.ours, id) {
+                    classes.push("ours");
+                }
+                if touches(&frame.theirs, id) {
+                    classes.push("theirs");
+                }
+                if !frame.is_visible(id) {
+                    classes.push("fog");
+                }
+                if region.wasteland {
+                    classes.push("wasteland");
+                }
+                page.push_str(&format!(
+                    "<span class=\"region {}\" title=\"{}\">{}: {}</span>\n",
+                    classes.join(" "),
+                    region.owner.as_str(),
+                    id,
+                    region.armies
+                ));
+            }
+            page.push_str("</div>\n");
+        }
+        page.push_str("</section>\n");
+    }
+    page.push_str(SCRIPT);
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+const STYLE: &'static str = "<style>
+body { font-family: sans-serif; }
+.round { display: none; }
+.round.shown { display: block; }
+.super { margin: 0.5em 0; }
+.super h3 { font-size: 1em; margin: 0.2em 0; }
+.region { display: inline-block; min-width: 4em; margin: 2px; padding: 2px 6px; border: 2px solid transparent; }
+.ally { background: #9cf; }
+.enemy { background: #f99; }
+.neutral { background: #ddd; }
+.ours { border-color: #036; }
+.theirs { border-color: #900; border-style: dashed; }
+.fog { opacity: 0.45; }
+.wasteland { font-weight: bold; }
+.legend span { padding: 2px 6px; border: 2px solid transparent; }
+</style>
+";
+
+const SCRIPT: &'static str = "<script>
+var rounds = document.querySelectorAll('.round');
+var current = 0;
+function show(index) {
+  if (index < 0 || index >= rounds.length) return;
+  rounds[current].classList.remove('shown');
+  current = index;
+  rounds[current].classList.add('shown');
+}
+document.getElementById('previous').onclick = function () { show(current - 1); };
+document.getElementById('next').onclick = function () { show(current + 1); };
+document.onkeydown = function (event) {
+  if (event.key == 'ArrowLeft') show(current - 1);
+  if (event.key == 'ArrowRight') show(current + 1);
+};
+if (rounds.length > 0) rounds[0].classList.add('shown');
+</script>
+";
+
+#[cfg(test)]
+const TEST_LOG: &'static str = "\
+player1 < settings your_bot player1
+player1 < settings opponent_bot player2
+player1 < setup_map super_regions 1 2 2 3
+player1 < setup_map regions 1 1 2 1 3 2
+player1 < setup_map neighbors 1 2 2 3
+player1 < settings starting_armies 5
+player1 < update_map 1 player1 2 2 neutral 2
+player1 < opponent_moves
+player1 < go place_armies 10000
+player1 > player1 place_armies 1 5
+player1 < go attack/transfer 10000
+player1 > player1 attack/transfer 1 2 6
+player1 < update_map 1 player1 1 2 player1 5 3 player2 4
+player1 < opponent_moves player2 place_armies 3 2
+player1 < go place_armies 10000
+player1 > No moves
+";
+
+#[test]
+fn rounds_are_seen_the_way_the_bot_saw_them() {
+    use fodder::from_engine_log;
+    let timeline = timeline(&from_engine_log(TEST_LOG, "player1").unwrap());
+    assert_eq!(timeline.frames.len(), 2);
+
+    let first = &timeline.frames[0];
+    assert_eq!(first.ours, vec![Move::PlaceArmies(1, 5), Move::AttackTransfer(1, 2, 6)]);
+    assert!(first.theirs.is_empty());
+    assert!(!first.is_visible(3));
+    let shown = text(&timeline, first);
+    assert!(shown.contains("our moves:   player1 place_armies 1 5, player1 attack/transfer 1 2 6"));
+    assert!(shown.contains("     1  ally        2  *\n"));
+    assert!(shown.contains("     3  neutral     2      fog\n"));
+
+    let second = &timeline.frames[1];
+    assert!(second.ours.is_empty());
+    assert_eq!(second.theirs, vec![Move::PlaceArmies(3, 2)]);
+    assert_eq!(second.map.region(3).unwrap().owner, OwnerValue::Enemy);
+    let shown = text(&timeline, second);
+    assert!(shown.contains("super region 1, bonus 2, ours"));
+    assert!(shown.contains("     3  enemy       4   !\n"));
+
+    let page = html(&timeline, "a <game>");
+    assert!(page.contains("<title>a &lt;game&gt;</title>"));
+    assert_eq!(page.matches("<section class=\"round\"").count(), 2);
+    assert!(page.contains("class=\"region enemy theirs\""));
+}
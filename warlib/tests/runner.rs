@@ -4,6 +4,8 @@ extern crate env_logger;
 
 extern crate warlib;
 
+use warlib::strategy::{self, StrategyOptions};
+
 use std::env;
 use std::fs::File;
 use std::io::Read;
@@ -15,6 +17,11 @@ include!(concat!(env!("OUT_DIR"), "/tests.rs"));
 
 static START: Once = ONCE_INIT;
 
+/// Fodder files are played with this seed unless `WARBOT_SEED` is set, so a
+/// failure can be rerun and other seeds tried. Every file has to pass
+/// whatever the seed.
+const SEED: u64 = 0;
+
 fn run_file(name: &str) {
     START.call_once(|| {
         env::set_var("RUST_LOG", "warn");
@@ -31,12 +38,25 @@ fn run_file(name: &str) {
         },
         Ok(f) => f
     };
-    let (tx, rx) = warlib::Bot::spawn();
+    let seed = env::var(warlib::SEED_VAR).ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(SEED);
 
     let mut last = String::new();
 
     let mut contents = String::new();
     file.read_to_string(&mut contents).unwrap();
+
+    // Files play the random strategy unless they start with a
+    // `# Strategy: <name>` line.
+    let strategy_name = contents.lines()
+        .find(|line| line.starts_with("# Strategy: "))
+        .map_or("random", |line| line.trim_start_matches("# Strategy: ").trim());
+    let strategy = match strategy::by_name(strategy_name, &StrategyOptions::default()) {
+        Some(strategy) => strategy,
+        None => panic!("unknown strategy {}", strategy_name)
+    };
+    let (tx, rx) = warlib::Bot::start_with(strategy, seed);
     for raw_line in contents.split('\n') {
         let line = raw_line.trim().to_owned();
         if line.len() == 0 || line.starts_with("Output") || line.starts_with("Round"){
@@ -76,7 +96,8 @@ fn run_file(name: &str) {
 enum TestSpec {
     Contains(String),
     ExactlyMatches(String),
-    NotContains(String)
+    NotContains(String),
+    Before(String, String)
 }
 
 fn run_spec (line: String, last: &String) {
@@ -92,6 +113,14 @@ fn run_spec (line: String, last: &String) {
             if last.contains(expected) {
                 panic!("Expected {} to not contain {}", last, expected);
             }
+        },
+        TestSpec::Before(ref first, ref second) => {
+            match (last.find(first), last.find(second)) {
+                (Some(first_at), Some(second_at)) => if first_at > second_at {
+                    panic!("Expected {} to come before {} in {}", first, second, last);
+                },
+                _ => panic!("Expected {} to contain {} and {}", last, first, second)
+            }
         }
     };
 }
@@ -107,6 +136,13 @@ fn parse_spec (mut spec: String) -> TestSpec {
         },
         '[' => {
             let container: &[_] = &['[', ']'];
+            if let Some(split) = spec.find("] before [") {
+                let (first, second) = spec.split_at(split);
+                return TestSpec::Before(
+                    first.trim_matches(container).to_owned(),
+                    second.trim_start_matches("] before ").trim_matches(container).to_owned()
+                );
+            }
             TestSpec::Contains(spec.trim_matches(container).to_owned())
         },
         _ => TestSpec::ExactlyMatches(spec)